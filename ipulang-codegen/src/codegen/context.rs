@@ -3,20 +3,31 @@ use std::collections::HashMap;
 use std::rc::Rc;
 
 use inkwell;
+use inkwell::basic_block::BasicBlock;
 use inkwell::builder::Builder;
 use inkwell::context::Context;
 use inkwell::module::Module;
 use inkwell::types::*;
 use inkwell::values::*;
+use inkwell::AddressSpace;
+use ipulang_parser::prelude::prelude;
 use ipulang_parser::types::Type;
 
+use super::runtime;
+
+/// break, continue の飛び先
+pub struct LoopTarget<'ll> {
+    pub continue_block: BasicBlock<'ll>,
+    pub break_block: BasicBlock<'ll>,
+}
+
 /// コード生成時のための情報
 pub struct Env<'ll> {
     pub module: Module<'ll>,
     pub ctx: &'ll Context,
-    /// function id -> variable id -> PointerValue
-    /// 宣言されている変数一覧
-    pub variables: HashMap<String, HashMap<String, PointerValue<'ll>>>,
+    /// variable id -> PointerValue
+    /// 宣言されている変数一覧. ブロックごとのスコープで, 内側のスコープほど後ろ
+    pub scopes: Vec<HashMap<String, PointerValue<'ll>>>,
     /// compilerが作った一時変数の個数
     pub var_count: Rc<Cell<usize>>,
     /// 宣言されている関数一覧
@@ -30,6 +41,17 @@ pub struct Env<'ll> {
     pub function: String,
     /// 現在の FunctionValue
     pub function_value: Option<FunctionValue<'ll>>,
+    /// 今いるループの飛び先. 内側のループほど後ろ
+    pub loop_targets: Vec<LoopTarget<'ll>>,
+    /// 文字列リテラル -> private な global 定数. 同じ文字列は使い回す
+    pub strings: HashMap<String, GlobalValue<'ll>>,
+    /// 配列の添字が範囲内かを実行時に調べるかどうか
+    pub bounds_check: bool,
+    /// 構造体名 -> LLVM の名前付き構造体型とフィールド名. フィールドは宣言した順
+    pub structs: HashMap<String, (StructType<'ll>, Vec<String>)>,
+    /// enum 名 -> LLVM の名前付き構造体型と, バリアントごとの名前と最初の値のフィールド番号
+    /// 0 番目のフィールドが discriminant で, バリアントの値は重ならないように後ろに並べる
+    pub enums: HashMap<String, (StructType<'ll>, Vec<(String, u32)>)>,
 }
 
 impl<'ll> Env<'ll> {
@@ -37,29 +59,52 @@ impl<'ll> Env<'ll> {
         // module
         let module = ctx.create_module("main");
 
-        let mut functions = HashMap::new();
-
-        // decrare putchar(i32): i32
-        let i32_type = ctx.i32_type();
-        let putchar_type = i32_type.fn_type(&[i32_type.into()], false);
-        let putchar_val = module.add_function("putchar", putchar_type, None);
-        functions.insert("putchar".to_owned(), putchar_val);
-
-        // decrate getchar(): i32
-        let getchar_type = i32_type.fn_type(&[], false);
-        let getchar_val = module.add_function("getchar", getchar_type, None);
-        functions.insert("getchar".to_owned(), getchar_val);
-
-        Self {
+        let mut env = Self {
             ctx: ctx,
             module: module,
-            variables: HashMap::new(),
+            scopes: vec![],
             var_count: Rc::new(Cell::new(0)),
-            functions: functions,
+            functions: HashMap::new(),
             builder: ctx.create_builder(),
             function: "".to_owned(),
             function_value: None,
+            loop_targets: vec![],
+            strings: HashMap::new(),
+            bounds_check: true,
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+        };
+        env.declare_builtins();
+        env
+    }
+
+    /// 組み込み関数を宣言する. ランタイムの関数は本体も作る
+    fn declare_builtins(&mut self) {
+        for decl in prelude() {
+            let params: Vec<Type> = decl.args.iter().map(|arg| arg.ty.clone()).collect();
+            let fn_value = self.declare_function(&decl.id, &params, &decl.ret_typ, decl.variadic);
+            self.functions.insert(decl.id, fn_value);
         }
+        runtime::define_runtime(self);
+    }
+
+    /// 関数をモジュールに追加する. `variadic` なら `params` の後ろに可変長引数を取る
+    pub fn declare_function(
+        &self,
+        id: &str,
+        params: &[Type],
+        ret: &Type,
+        variadic: bool,
+    ) -> FunctionValue<'ll> {
+        let params: Vec<BasicMetadataTypeEnum> = params
+            .iter()
+            .map(|ty| self.get_llvm_value_type(ty))
+            .collect();
+        let fn_type = match self.get_llvm_fn_type(ret) {
+            Some(ret_type) => ret_type.fn_type(&params, variadic),
+            None => self.ctx.void_type().fn_type(&params, variadic),
+        };
+        self.module.add_function(id, fn_type, None)
     }
 
     pub fn get_tmp_var_id(&self) -> String {
@@ -74,26 +119,36 @@ impl<'ll> Env<'ll> {
         format!("label{}", self.var_count.get().to_string())
     }
 
-    /// 関数に変数があるかどうか
-    pub fn contains(&self, name: String) -> bool {
-        self.variables
-            .get(&self.function)
-            .map(|m: &HashMap<String, PointerValue>| m.contains_key(&name))
+    /// 現在のブロックが return, break などで既に終わっているかどうか
+    pub fn is_terminated(&self) -> bool {
+        self.builder
+            .get_insert_block()
+            .map(|block| block.get_terminator().is_some())
             .unwrap_or(false)
     }
 
-    /// 関数に宣言されている変数を取得
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// 変数が見えるかどうか
+    pub fn contains(&self, name: String) -> bool {
+        self.get_variable(name).is_some()
+    }
+
+    /// 宣言されている変数を内側のスコープから探す
     pub fn get_variable(&self, name: String) -> Option<&'_ PointerValue<'ll>> {
-        self.variables
-            .get(&self.function)
-            .map(|m| m.get(&name))
-            .flatten()
+        self.scopes.iter().rev().find_map(|scope| scope.get(&name))
     }
 
-    /// 関数に変数情報を登録する
+    /// 今のスコープに変数情報を登録する
     pub fn set_variable(&mut self, name: String, value: PointerValue<'ll>) {
-        let map = self.variables.get_mut(&self.function).unwrap();
-        map.insert(name, value);
+        let scope = self.scopes.last_mut().unwrap();
+        scope.insert(name, value);
     }
 
     /// PointerValueをIntValueに変換する
@@ -119,22 +174,54 @@ impl<'ll> Env<'ll> {
         }
     }
 
-    pub fn get_llvm_fn_type(&self, typ: Type) -> Option<BasicTypeEnum<'ll>> {
+    /// 文字列リテラルの先頭を指す `i8*`. 中身は private な global 定数に置く
+    pub fn get_string(&mut self, value: &str) -> PointerValue<'ll> {
+        if let Some(global) = self.strings.get(value) {
+            return global.as_pointer_value();
+        }
+        let name = format!(".str{}", self.strings.len());
+        let global = self.builder.build_global_string_ptr(value, &name);
+        self.strings.insert(value.to_owned(), global);
+        global.as_pointer_value()
+    }
+
+    /// 値の型. 文字列は NUL 終端の `i8*`
+    pub fn get_llvm_type(&self, typ: &Type) -> BasicTypeEnum<'ll> {
         match typ {
-            Type::Unit => None,
-            Type::Int32 => Some(self.ctx.i32_type().into()),
-            Type::Int64 => Some(self.ctx.i64_type().into()),
-            Type::Bool => Some(self.ctx.bool_type().into()),
+            Type::Int32 | Type::UInt32 => self.ctx.i32_type().into(),
+            Type::Int64 | Type::UInt64 => self.ctx.i64_type().into(),
+            Type::Bool => self.ctx.bool_type().into(),
+            Type::String => self.ctx.i8_type().ptr_type(AddressSpace::Generic).into(),
+            Type::Array(elem, len) => self.get_llvm_type(elem).array_type(*len as u32).into(),
+            Type::Pointer(inner) => self
+                .get_llvm_type(inner)
+                .ptr_type(AddressSpace::Generic)
+                .into(),
+            Type::Named(name) => match self.structs.get(name) {
+                Some((struct_type, _)) => (*struct_type).into(),
+                None => self.enums[name].0.into(),
+            },
             _ => panic!("type: {} is unknown", typ),
         }
     }
 
-    pub fn get_llvm_value_type(&self, typ: Type) -> BasicMetadataTypeEnum<'ll> {
+    pub fn get_llvm_fn_type(&self, typ: &Type) -> Option<BasicTypeEnum<'ll>> {
         match typ {
-            Type::Int32 => self.ctx.i32_type().into(),
-            Type::Int64 => self.ctx.i64_type().into(),
-            Type::Bool => self.ctx.bool_type().into(),
+            Type::Unit => None,
+            _ => Some(self.get_llvm_type(typ)),
+        }
+    }
+
+    pub fn get_llvm_int_type(&self, typ: &Type) -> IntType<'ll> {
+        match typ {
+            Type::Int32 | Type::UInt32 => self.ctx.i32_type(),
+            Type::Int64 | Type::UInt64 => self.ctx.i64_type(),
+            Type::Bool => self.ctx.bool_type(),
             _ => panic!("type: {} is unknown", typ),
         }
     }
+
+    pub fn get_llvm_value_type(&self, typ: &Type) -> BasicMetadataTypeEnum<'ll> {
+        self.get_llvm_type(typ).into()
+    }
 }
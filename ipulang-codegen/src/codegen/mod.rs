@@ -1,21 +1,46 @@
 pub mod context;
+pub mod runtime;
 
-use anyhow::{Error, Result};
 use inkwell;
+use inkwell::basic_block::BasicBlock;
 use inkwell::context::Context;
 use inkwell::types::*;
 use inkwell::values::*;
+use ipulang_parser::diagnostics::Diagnostic;
 use ipulang_parser::nodes::*;
 use ipulang_parser::types::Type;
 
-use self::context::Env;
+use self::context::{Env, LoopTarget};
 
 type VoidValue<'ll> = IntValue<'ll>;
 
-pub fn code_gen(ast: Program) -> Result<String, Box<Error>> {
+/// コード生成の設定
+pub struct Options {
+    /// 配列の添字が範囲内かを実行時に調べる. 外すと範囲外を読み書きしても止まらない
+    pub bounds_check: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self { bounds_check: true }
+    }
+}
+
+pub fn code_gen(ast: Program) -> Result<String, Diagnostic> {
+    code_gen_with_options(ast, &Options::default())
+}
+
+pub fn code_gen_with_options(ast: Program, options: &Options) -> Result<String, Diagnostic> {
     let context = Context::create();
     let mut env = Env::new(&context);
+    env.bounds_check = options.bounds_check;
     ast.code_gen(&mut env);
+    // 型検査を通っていれば壊れた IR にはならないはず
+    env.module.verify().map_err(|e| {
+        Diagnostic::error("E0201", "generated invalid LLVM IR")
+            .with_note(e.to_string())
+            .with_help("this is a bug in the compiler")
+    })?;
     Ok(env.module.print_to_string().to_string())
 }
 
@@ -28,15 +53,96 @@ impl<'ll> CodeGen<'ll, IntValue<'ll>> for Const {
         match self {
             Const::I32Const(i) => Some(env.ctx.i32_type().const_int(i as u64, false)),
             Const::I64Const(i) => Some(env.ctx.i64_type().const_int(i as u64, false)),
+            // LLVM の整数型には符号がないので, ビット列をそのまま使う
+            Const::U32Const(i) => Some(env.ctx.i32_type().const_int(i as u64, false)),
+            Const::U64Const(i) => Some(env.ctx.i64_type().const_int(i, false)),
             Const::BoolConst(b) => Some(env.ctx.bool_type().const_int(b as u64, false)),
             _ => panic!("not support"),
         }
     }
 }
 
+impl<'ll> CodeGen<'ll, IntValue<'ll>> for IntLiteral<'ll> {
+    fn code_gen(self, env: &mut Env<'ll>) -> Option<IntValue<'ll>> {
+        // 型検査で型に収まることは確かめてある. 負の値も下位ビットをそのまま使えばよい
+        let int_type = env.get_llvm_int_type(&self.ty);
+        Some(int_type.const_int(self.value as u64, false))
+    }
+}
+
+/// && と || は左辺だけで結果が決まる時は右辺を評価しない
+fn short_circuit_code_gen<'ll>(
+    bin_op: BinOp<'ll>,
+    env: &mut Env<'ll>,
+) -> Option<PointerValue<'ll>> {
+    //   <left>
+    //   && : jmp <rhs> if left else <dest>
+    //   || : jmp <dest> if left else <rhs>
+    // rhs:
+    //   <right>
+    //   jmp dest
+    // dest:
+    //   phi [left, lhs], [right, rhs]
+    let tmp_id = env.get_tmp_var_id();
+    let ptr_lhs = bin_op.left.code_gen(env).unwrap();
+    let load_lhs = env.builder.build_load(ptr_lhs, &tmp_id).into_int_value();
+    let lhs_block = env.builder.get_insert_block().unwrap();
+
+    let fn_value = env.function_value.clone().unwrap();
+    let rhs_label = env.get_tmp_label_id();
+    let rhs_block = env.ctx.append_basic_block(fn_value, &rhs_label);
+    let dest_label = env.get_tmp_label_id();
+    let dest_block = env.ctx.append_basic_block(fn_value, &dest_label);
+
+    match bin_op.op {
+        Op::And => env
+            .builder
+            .build_conditional_branch(load_lhs, rhs_block, dest_block),
+        Op::Or => env
+            .builder
+            .build_conditional_branch(load_lhs, dest_block, rhs_block),
+        _ => unreachable!("{:?} is not a short-circuit operator", bin_op.op),
+    };
+
+    env.builder.position_at_end(rhs_block);
+    let tmp_id = env.get_tmp_var_id();
+    let ptr_rhs = bin_op.right.code_gen(env).unwrap();
+    let load_rhs = env.builder.build_load(ptr_rhs, &tmp_id).into_int_value();
+    // 右辺の中でブロックが増えているかもしれない
+    let rhs_end_block = env.builder.get_insert_block().unwrap();
+    env.builder.build_unconditional_branch(dest_block);
+
+    // 左辺で決まった時は左辺の値がそのまま結果になる
+    env.builder.position_at_end(dest_block);
+    let bool_type = env.ctx.bool_type();
+    let tmp_id = env.get_tmp_var_id();
+    let phi = env.builder.build_phi(bool_type, &tmp_id);
+    phi.add_incoming(&[(&load_lhs, lhs_block), (&load_rhs, rhs_end_block)]);
+
+    let tmp_id = env.get_tmp_var_id();
+    let ptr = env.builder.build_alloca(bool_type, &tmp_id);
+    env.builder
+        .build_store(ptr, phi.as_basic_value().into_int_value());
+    Some(ptr)
+}
+
 impl<'ll> CodeGen<'ll, PointerValue<'ll>> for BinOp<'ll> {
     fn code_gen(self, env: &mut Env<'ll>) -> Option<PointerValue<'ll>> {
+        if let Op::And | Op::Or = self.op {
+            return short_circuit_code_gen(self, env);
+        }
+
         let bool_type = env.ctx.bool_type();
+        // 比較, 割り算, 右シフトは符号の有無で命令が変わる
+        let signed = self.left.ty().is_signed();
+        let rhs_signed = self.right.ty().is_signed();
+        let predicate = |signed_pred, unsigned_pred| {
+            if signed {
+                signed_pred
+            } else {
+                unsigned_pred
+            }
+        };
 
         let tmp_id = env.get_tmp_var_id();
         let ptr_lhr = self.left.code_gen(env).unwrap();
@@ -48,11 +154,7 @@ impl<'ll> CodeGen<'ll, PointerValue<'ll>> for BinOp<'ll> {
         let tmp_id = env.get_tmp_var_id();
 
         let (tmp, result_type) = match self.op {
-            Op::Or => (env.builder.build_or(load_lhs, load_rhs, &tmp_id), bool_type),
-            Op::And => (
-                env.builder.build_and(load_lhs, load_rhs, &tmp_id),
-                bool_type,
-            ),
+            Op::Or | Op::And => unreachable!(),
             Op::Eq => (
                 env.builder.build_int_compare(
                     inkwell::IntPredicate::EQ,
@@ -73,7 +175,7 @@ impl<'ll> CodeGen<'ll, PointerValue<'ll>> for BinOp<'ll> {
             ),
             Op::Geq => (
                 env.builder.build_int_compare(
-                    inkwell::IntPredicate::SGE,
+                    predicate(inkwell::IntPredicate::SGE, inkwell::IntPredicate::UGE),
                     load_lhs,
                     load_rhs,
                     &tmp_id,
@@ -82,7 +184,7 @@ impl<'ll> CodeGen<'ll, PointerValue<'ll>> for BinOp<'ll> {
             ),
             Op::Leq => (
                 env.builder.build_int_compare(
-                    inkwell::IntPredicate::SLE,
+                    predicate(inkwell::IntPredicate::SLE, inkwell::IntPredicate::ULE),
                     load_lhs,
                     load_rhs,
                     &tmp_id,
@@ -91,7 +193,7 @@ impl<'ll> CodeGen<'ll, PointerValue<'ll>> for BinOp<'ll> {
             ),
             Op::Gt => (
                 env.builder.build_int_compare(
-                    inkwell::IntPredicate::SGT,
+                    predicate(inkwell::IntPredicate::SGT, inkwell::IntPredicate::UGT),
                     load_lhs,
                     load_rhs,
                     &tmp_id,
@@ -100,7 +202,7 @@ impl<'ll> CodeGen<'ll, PointerValue<'ll>> for BinOp<'ll> {
             ),
             Op::Lt => (
                 env.builder.build_int_compare(
-                    inkwell::IntPredicate::SLT,
+                    predicate(inkwell::IntPredicate::SLT, inkwell::IntPredicate::ULT),
                     load_lhs,
                     load_rhs,
                     &tmp_id,
@@ -119,16 +221,42 @@ impl<'ll> CodeGen<'ll, PointerValue<'ll>> for BinOp<'ll> {
                 env.builder.build_int_mul(load_lhs, load_rhs, &tmp_id),
                 load_lhs.get_type(),
             ),
-            Op::Div => (
+            Op::Div if signed => (
                 env.builder
                     .build_int_signed_div(load_lhs, load_rhs, &tmp_id),
                 load_lhs.get_type(),
             ),
-            Op::Mod => (
+            Op::Div => (
+                env.builder
+                    .build_int_unsigned_div(load_lhs, load_rhs, &tmp_id),
+                load_lhs.get_type(),
+            ),
+            Op::Mod if signed => (
                 env.builder
                     .build_int_signed_rem(load_lhs, load_rhs, &tmp_id),
                 load_lhs.get_type(),
             ),
+            Op::Mod => (
+                env.builder
+                    .build_int_unsigned_rem(load_lhs, load_rhs, &tmp_id),
+                load_lhs.get_type(),
+            ),
+            Op::Shl => {
+                let rhs = resize_int(env, load_rhs, load_lhs.get_type(), rhs_signed);
+                (
+                    env.builder.build_left_shift(load_lhs, rhs, &tmp_id),
+                    load_lhs.get_type(),
+                )
+            }
+            // 符号付きは算術シフト, 符号なしは論理シフト
+            Op::Shr => {
+                let rhs = resize_int(env, load_rhs, load_lhs.get_type(), rhs_signed);
+                (
+                    env.builder
+                        .build_right_shift(load_lhs, rhs, signed, &tmp_id),
+                    load_lhs.get_type(),
+                )
+            }
         };
 
         let tmp_id = env.get_tmp_var_id();
@@ -138,25 +266,107 @@ impl<'ll> CodeGen<'ll, PointerValue<'ll>> for BinOp<'ll> {
     }
 }
 
+/// 整数のビット幅を `to` に合わせる. 広げる時は符号の有無で sext か zext を選ぶ
+fn resize_int<'ll>(
+    env: &mut Env<'ll>,
+    value: IntValue<'ll>,
+    to: IntType<'ll>,
+    signed: bool,
+) -> IntValue<'ll> {
+    let from_width = value.get_type().get_bit_width();
+    let to_width = to.get_bit_width();
+    let tmp_id = env.get_tmp_var_id();
+    if from_width < to_width {
+        if signed {
+            env.builder.build_int_s_extend(value, to, &tmp_id)
+        } else {
+            env.builder.build_int_z_extend(value, to, &tmp_id)
+        }
+    } else if from_width > to_width {
+        env.builder.build_int_truncate(value, to, &tmp_id)
+    } else {
+        value
+    }
+}
+
+impl<'ll> CodeGen<'ll, PointerValue<'ll>> for UnaryOp<'ll> {
+    fn code_gen(self, env: &mut Env<'ll>) -> Option<PointerValue<'ll>> {
+        match self.op {
+            // 式は値を指すポインタになっているので, それがそのままアドレスになる
+            UnOp::Ref => {
+                let ptr = self.expr.code_gen(env).unwrap();
+                let tmp_id = env.get_tmp_var_id();
+                let slot = env.builder.build_alloca(ptr.get_type(), &tmp_id);
+                env.builder.build_store(slot, ptr);
+                return Some(slot);
+            }
+            // ポインタの値を読めば, 指している先を返せばよい. 代入先にも使える
+            UnOp::Deref => {
+                let ptr = self.expr.code_gen(env).unwrap();
+                let tmp_id = env.get_tmp_var_id();
+                let load = env.builder.build_load(ptr, &tmp_id).into_pointer_value();
+                return Some(load);
+            }
+            _ => {}
+        }
+        let tmp_id = env.get_tmp_var_id();
+        let ptr = self.expr.code_gen(env).unwrap();
+        let load = env.builder.build_load(ptr, &tmp_id).into_int_value();
+
+        let tmp_id = env.get_tmp_var_id();
+        let tmp = match self.op {
+            UnOp::Neg => env.builder.build_int_neg(load, &tmp_id),
+            // bool は i1 なので not でそのまま反転できる
+            UnOp::Not | UnOp::BitNot => env.builder.build_not(load, &tmp_id),
+            UnOp::Ref | UnOp::Deref => unreachable!(),
+        };
+
+        let tmp_id = env.get_tmp_var_id();
+        let ptr = env.builder.build_alloca(load.get_type(), &tmp_id);
+        env.builder.build_store(ptr, tmp);
+        Some(ptr)
+    }
+}
+
+impl<'ll> CodeGen<'ll, PointerValue<'ll>> for Cast<'ll> {
+    fn code_gen(self, env: &mut Env<'ll>) -> Option<PointerValue<'ll>> {
+        // bool は 0, 1 に広げるので符号なしとして扱う
+        let signed = self.expr.ty().is_signed();
+        let tmp_id = env.get_tmp_var_id();
+        let ptr = self.expr.code_gen(env).unwrap();
+        let load = env.builder.build_load(ptr, &tmp_id).into_int_value();
+
+        let to = env.get_llvm_int_type(&self.ty);
+        let tmp = resize_int(env, load, to, signed);
+
+        let tmp_id = env.get_tmp_var_id();
+        let ptr = env.builder.build_alloca(to, &tmp_id);
+        env.builder.build_store(ptr, tmp);
+        Some(ptr)
+    }
+}
+
 impl<'ll> CodeGen<'ll, IntValue<'ll>> for VariableDecl<'ll> {
     fn code_gen(self, env: &mut Env<'ll>) -> Option<IntValue<'ll>> {
-        let var_type = match self.ty {
-            Type::Int32 => env.ctx.i32_type(),
-            Type::Int64 => env.ctx.i64_type(),
-            Type::Bool => env.ctx.bool_type(),
-            _ => panic!("ty: {:?} is unknown", self.ty),
-        };
+        let var_type = env.get_llvm_type(&self.ty);
         if let Some(init) = self.init {
             let init_ptr = init.code_gen(env).unwrap();
             let tmp_id = env.get_tmp_var_id();
             let tmp = env.builder.build_load(init_ptr, &tmp_id);
             let ptr: PointerValue = env.builder.build_alloca(var_type, &self.id);
-            env.builder.build_store(ptr, tmp.into_int_value());
+            env.builder.build_store(ptr, tmp);
 
             env.set_variable(self.id.clone(), ptr);
         } else {
             let ptr: PointerValue = env.builder.build_alloca(var_type, &self.id);
-            let zero = env.ctx.i32_type().const_int(0, false);
+            // 文字列は空文字列, ポインタは null, 配列と構造体は全ての要素を 0 で初期化する
+            let zero: BasicValueEnum = match &self.ty {
+                Type::String => env.get_string("").into(),
+                Type::Array(..) => var_type.into_array_type().const_zero().into(),
+                Type::Pointer(_) => var_type.into_pointer_type().const_null().into(),
+                Type::Named(_) => var_type.into_struct_type().const_zero().into(),
+                typ => env.get_llvm_int_type(typ).const_zero().into(),
+            };
             env.builder.build_store(ptr, zero);
             env.set_variable(self.id.clone(), ptr);
         }
@@ -170,9 +380,309 @@ impl<'ll> CodeGen<'ll, PointerValue<'ll>> for Variable<'ll> {
     }
 }
 
+impl<'ll> CodeGen<'ll, PointerValue<'ll>> for ArrayLiteral<'ll> {
+    fn code_gen(self, env: &mut Env<'ll>) -> Option<PointerValue<'ll>> {
+        let array_type = env.get_llvm_type(&self.ty);
+        let tmp_id = env.get_tmp_var_id();
+        let ptr = env.builder.build_alloca(array_type, &tmp_id);
+        let i64_type = env.ctx.i64_type();
+        for (i, elem) in self.elems.into_iter().enumerate() {
+            let elem_ptr = elem.code_gen(env).unwrap();
+            let tmp_id = env.get_tmp_var_id();
+            let value = env.builder.build_load(elem_ptr, &tmp_id);
+            let indexes = [i64_type.const_zero(), i64_type.const_int(i as u64, false)];
+            let tmp_id = env.get_tmp_var_id();
+            let dest = unsafe { env.builder.build_in_bounds_gep(ptr, &indexes, &tmp_id) };
+            env.builder.build_store(dest, value);
+        }
+        Some(ptr)
+    }
+}
+
+/// 添字が `0..len` に無ければ行番号付きのメッセージを出して abort する
+fn bounds_check_code_gen<'ll>(env: &mut Env<'ll>, index: IntValue<'ll>, len: u64, line: u32) {
+    //   jmp ok if index < len else fail  (負の添字は符号なしで比べると大きくなる)
+    // fail:
+    //   print_str(...), print_i64(index), println()
+    //   abort()
+    // ok:
+    let fn_value = env.function_value.clone().unwrap();
+    let fail_label = env.get_tmp_label_id();
+    let fail_block = env.ctx.append_basic_block(fn_value, &fail_label);
+    let ok_label = env.get_tmp_label_id();
+    let ok_block = env.ctx.append_basic_block(fn_value, &ok_label);
+
+    let len_value = index.get_type().const_int(len, false);
+    let tmp_id = env.get_tmp_var_id();
+    let in_bounds =
+        env.builder
+            .build_int_compare(inkwell::IntPredicate::ULT, index, len_value, &tmp_id);
+    env.builder
+        .build_conditional_branch(in_bounds, ok_block, fail_block);
+
+    env.builder.position_at_end(fail_block);
+    let message = env.get_string(&format!(
+        "index out of bounds at line {}: the length is {} but the index is ",
+        line, len
+    ));
+    env.builder
+        .build_call(env.functions["print_str"], &[message.into()], "");
+    env.builder
+        .build_call(env.functions["print_i64"], &[index.into()], "");
+    env.builder.build_call(env.functions["println"], &[], "");
+    env.builder.build_call(env.functions["abort"], &[], "");
+    env.builder.build_unreachable();
+
+    env.builder.position_at_end(ok_block);
+}
+
+impl<'ll> CodeGen<'ll, PointerValue<'ll>> for Index<'ll> {
+    /// 要素へのポインタを返すので, そのまま代入先にも使える
+    fn code_gen(self, env: &mut Env<'ll>) -> Option<PointerValue<'ll>> {
+        let len = match self.array.ty() {
+            Type::Array(_, len) => len,
+            typ => unreachable!("type: {} is not an array", typ),
+        };
+        let signed = self.index.ty().is_signed();
+        let line = self.position.location_line();
+
+        let array_ptr = self.array.code_gen(env).unwrap();
+        let index_ptr = self.index.code_gen(env).unwrap();
+        let tmp_id = env.get_tmp_var_id();
+        let index = env.builder.build_load(index_ptr, &tmp_id).into_int_value();
+        let i64_type = env.ctx.i64_type();
+        let index = resize_int(env, index, i64_type, signed);
+
+        if env.bounds_check {
+            bounds_check_code_gen(env, index, len, line);
+        }
+
+        let indexes = [i64_type.const_zero(), index];
+        let tmp_id = env.get_tmp_var_id();
+        let ptr = unsafe {
+            env.builder
+                .build_in_bounds_gep(array_ptr, &indexes, &tmp_id)
+        };
+        Some(ptr)
+    }
+}
+
+impl<'ll> CodeGen<'ll, PointerValue<'ll>> for StructLiteral<'ll> {
+    fn code_gen(self, env: &mut Env<'ll>) -> Option<PointerValue<'ll>> {
+        let (struct_type, names) = env.structs[&self.id].clone();
+        let tmp_id = env.get_tmp_var_id();
+        let ptr = env.builder.build_alloca(struct_type, &tmp_id);
+        // フィールドは書いた順に評価する
+        for field in self.fields {
+            let index = names.iter().position(|name| *name == field.id).unwrap();
+            let value_ptr = field.value.code_gen(env).unwrap();
+            let tmp_id = env.get_tmp_var_id();
+            let value = env.builder.build_load(value_ptr, &tmp_id);
+            let tmp_id = env.get_tmp_var_id();
+            let dest = env
+                .builder
+                .build_struct_gep(ptr, index as u32, &tmp_id)
+                .unwrap();
+            env.builder.build_store(dest, value);
+        }
+        Some(ptr)
+    }
+}
+
+impl<'ll> CodeGen<'ll, PointerValue<'ll>> for Field<'ll> {
+    /// フィールドへのポインタを返すので, そのまま代入先にも使える
+    fn code_gen(self, env: &mut Env<'ll>) -> Option<PointerValue<'ll>> {
+        let index = match self.expr.ty() {
+            Type::Named(name) => env.structs[&name]
+                .1
+                .iter()
+                .position(|field| *field == self.id)
+                .unwrap(),
+            typ => unreachable!("type: {} is not a struct", typ),
+        };
+        let ptr = self.expr.code_gen(env).unwrap();
+        let tmp_id = env.get_tmp_var_id();
+        Some(
+            env.builder
+                .build_struct_gep(ptr, index as u32, &tmp_id)
+                .unwrap(),
+        )
+    }
+}
+
+impl<'ll> CodeGen<'ll, PointerValue<'ll>> for VariantLiteral<'ll> {
+    fn code_gen(self, env: &mut Env<'ll>) -> Option<PointerValue<'ll>> {
+        let (enum_type, variants) = env.enums[&self.enum_id].clone();
+        let tag = variants
+            .iter()
+            .position(|(id, _)| *id == self.variant)
+            .unwrap();
+        let first = variants[tag].1;
+        let tmp_id = env.get_tmp_var_id();
+        let ptr = env.builder.build_alloca(enum_type, &tmp_id);
+
+        let tmp_id = env.get_tmp_var_id();
+        let tag_ptr = env.builder.build_struct_gep(ptr, 0, &tmp_id).unwrap();
+        let tag = env.ctx.i32_type().const_int(tag as u64, false);
+        env.builder.build_store(tag_ptr, tag);
+        for (i, arg) in self.args.into_iter().enumerate() {
+            let value_ptr = arg.code_gen(env).unwrap();
+            let tmp_id = env.get_tmp_var_id();
+            let value = env.builder.build_load(value_ptr, &tmp_id);
+            let tmp_id = env.get_tmp_var_id();
+            let dest = env
+                .builder
+                .build_struct_gep(ptr, first + i as u32, &tmp_id)
+                .unwrap();
+            env.builder.build_store(dest, value);
+        }
+        Some(ptr)
+    }
+}
+
+impl<'ll> CodeGen<'ll, PointerValue<'ll>> for Match<'ll> {
+    /// discriminant で switch する. 式の match なら値を入れた場所を返す
+    fn code_gen(self, env: &mut Env<'ll>) -> Option<PointerValue<'ll>> {
+        //   switch tag, default [tag0, arm0], [tag1, arm1] ...
+        // arm0:
+        //   フィールドを変数にコピーして腕の中身
+        //   br dest
+        // ...
+        // dest:
+        let result = match &self.ty {
+            Type::Unit => None,
+            ty => {
+                let result_type = env.get_llvm_type(ty);
+                let tmp_id = env.get_tmp_var_id();
+                Some(env.builder.build_alloca(result_type, &tmp_id))
+            }
+        };
+        let variants = match self.expr.ty() {
+            Type::Named(name) => env.enums[&name].1.clone(),
+            typ => unreachable!("type: {} is not an enum", typ),
+        };
+
+        let ptr = self.expr.code_gen(env).unwrap();
+        let tmp_id = env.get_tmp_var_id();
+        let tag_ptr = env.builder.build_struct_gep(ptr, 0, &tmp_id).unwrap();
+        let tmp_id = env.get_tmp_var_id();
+        let tag = env.builder.build_load(tag_ptr, &tmp_id).into_int_value();
+
+        let fn_value = env.function_value.clone().unwrap();
+        let mut arm_blocks: Vec<BasicBlock> = vec![];
+        for _ in self.arms.iter() {
+            let label_id = env.get_tmp_label_id();
+            arm_blocks.push(env.ctx.append_basic_block(fn_value, &label_id));
+        }
+        let label_id = env.get_tmp_label_id();
+        let dest_block = env.ctx.append_basic_block(fn_value, &label_id);
+
+        // 同じバリアントは最初の腕に, `_` より後ろのバリアントは `_` の腕に飛ぶ
+        let mut cases: Vec<(IntValue, BasicBlock)> = vec![];
+        let mut seen: Vec<usize> = vec![];
+        let mut default_block = None;
+        for (arm, block) in self.arms.iter().zip(arm_blocks.iter()) {
+            match &arm.pattern {
+                _ if default_block.is_some() => {}
+                Pattern::Wildcard(_) => default_block = Some(*block),
+                Pattern::Variant(pattern) => {
+                    let tag = variants
+                        .iter()
+                        .position(|(id, _)| *id == pattern.variant)
+                        .unwrap();
+                    if !seen.contains(&tag) {
+                        seen.push(tag);
+                        let tag = env.ctx.i32_type().const_int(tag as u64, false);
+                        cases.push((tag, *block));
+                    }
+                }
+            }
+        }
+        match default_block {
+            Some(default_block) => {
+                env.builder.build_switch(tag, default_block, &cases);
+            }
+            // 型検査で全てのバリアントに腕があると分かっているので, ここには来ない
+            None => {
+                let label_id = env.get_tmp_label_id();
+                let unreachable_block = env.ctx.append_basic_block(fn_value, &label_id);
+                env.builder.build_switch(tag, unreachable_block, &cases);
+                env.builder.position_at_end(unreachable_block);
+                env.builder.build_unreachable();
+            }
+        }
+
+        let mut reached = false;
+        for (arm, block) in self.arms.into_iter().zip(arm_blocks) {
+            env.builder.position_at_end(block);
+            // フィールドを入れる変数は腕の中だけで見える
+            env.push_scope();
+            if let Pattern::Variant(pattern) = arm.pattern {
+                let first = variants
+                    .iter()
+                    .find(|(id, _)| *id == pattern.variant)
+                    .unwrap()
+                    .1;
+                for (i, binding) in pattern.bindings.into_iter().enumerate() {
+                    if binding.id == "_" {
+                        continue;
+                    }
+                    let tmp_id = env.get_tmp_var_id();
+                    let field_ptr = env
+                        .builder
+                        .build_struct_gep(ptr, first + i as u32, &tmp_id)
+                        .unwrap();
+                    let tmp_id = env.get_tmp_var_id();
+                    let value = env.builder.build_load(field_ptr, &tmp_id);
+                    let var_ptr = env.builder.build_alloca(value.get_type(), &binding.id);
+                    env.builder.build_store(var_ptr, value);
+                    env.set_variable(binding.id, var_ptr);
+                }
+            }
+            match arm.body {
+                ArmBody::Block(stmts) => {
+                    stmts.code_gen(env);
+                }
+                ArmBody::Expr(expr) => {
+                    let value_ptr = expr.code_gen(env);
+                    if let (Some(result), Some(value_ptr)) = (result, value_ptr) {
+                        let tmp_id = env.get_tmp_var_id();
+                        let value = env.builder.build_load(value_ptr, &tmp_id);
+                        env.builder.build_store(result, value);
+                    }
+                }
+            }
+            env.pop_scope();
+            if !env.is_terminated() {
+                env.builder.build_unconditional_branch(dest_block);
+                reached = true;
+            }
+        }
+
+        // dest は最後のブロックにする
+        let last_block = fn_value.get_last_basic_block().unwrap();
+        if last_block != dest_block {
+            dest_block.move_after(last_block).unwrap();
+        }
+        env.builder.position_at_end(dest_block);
+        // 全ての腕が return などで抜けていたら, ここには来ない
+        if !reached {
+            env.builder.build_unreachable();
+        }
+        result
+    }
+}
+
 impl<'ll> CodeGen<'ll, PointerValue<'ll>> for Expr<'ll> {
     fn code_gen(self, env: &mut Env<'ll>) -> Option<PointerValue<'ll>> {
         match self {
+            Expr::Const(Const::Str(value)) => {
+                let tmp = env.get_string(&value);
+                let tmp_id = env.get_tmp_var_id();
+                let ptr = env.builder.build_alloca(tmp.get_type(), &tmp_id);
+                env.builder.build_store(ptr, tmp);
+                Some(ptr)
+            }
             Expr::Const(cns) => {
                 let tmp = cns.code_gen(env).unwrap();
                 let tmp_id = env.get_tmp_var_id();
@@ -180,8 +690,23 @@ impl<'ll> CodeGen<'ll, PointerValue<'ll>> for Expr<'ll> {
                 env.builder.build_store(ptr, tmp);
                 Some(ptr)
             }
+            Expr::IntLiteral(lit) => {
+                let tmp = lit.code_gen(env).unwrap();
+                let tmp_id = env.get_tmp_var_id();
+                let ptr = env.builder.build_alloca(tmp.get_type(), &tmp_id);
+                env.builder.build_store(ptr, tmp);
+                Some(ptr)
+            }
             Expr::BinOp(bin_op) => bin_op.code_gen(env),
+            Expr::UnaryOp(unary_op) => unary_op.code_gen(env),
+            Expr::Cast(cast) => cast.code_gen(env),
             Expr::Variable(var) => var.code_gen(env),
+            Expr::ArrayLiteral(array) => array.code_gen(env),
+            Expr::Index(index) => index.code_gen(env),
+            Expr::StructLiteral(literal) => literal.code_gen(env),
+            Expr::Field(field) => field.code_gen(env),
+            Expr::VariantLiteral(literal) => literal.code_gen(env),
+            Expr::Match(m) => m.code_gen(env),
             Expr::Call(call) => {
                 // always returns value
                 let call_id = call.id.clone();
@@ -190,19 +715,10 @@ impl<'ll> CodeGen<'ll, PointerValue<'ll>> for Expr<'ll> {
                 let f = env.functions.get(&call_id).unwrap();
                 // 関数がvoidを返すならNoneを返す
                 if let Some(ret_type) = f.get_type().get_return_type() {
-                    match ret_type {
-                        BasicTypeEnum::IntType(int_type)
-                            if int_type == env.ctx.i32_type() || int_type == env.ctx.i64_type() =>
-                        {
-                            let ptr = env.builder.build_alloca(int_type, &tmp_id);
-                            env.builder.build_store(
-                                ptr,
-                                callsitevalu.try_as_basic_value().left().unwrap(),
-                            );
-                            Some(ptr)
-                        }
-                        _ => panic!(""),
-                    }
+                    let ptr = env.builder.build_alloca(ret_type, &tmp_id);
+                    env.builder
+                        .build_store(ptr, callsitevalu.try_as_basic_value().left().unwrap());
+                    Some(ptr)
                 } else {
                     None
                 }
@@ -213,76 +729,127 @@ impl<'ll> CodeGen<'ll, PointerValue<'ll>> for Expr<'ll> {
 
 impl<'ll> CodeGen<'ll, CallSiteValue<'ll>> for Call<'ll> {
     fn code_gen(self, env: &mut Env<'ll>) -> Option<CallSiteValue<'ll>> {
+        let function = env.module.get_function(&self.id).unwrap();
+        let fixed_params = function.count_params() as usize;
+
         // eval exprs
         let mut evaluated_args: Vec<BasicMetadataValueEnum> = vec![];
-        for arg in self.args {
+        for (i, arg) in self.args.into_iter().enumerate() {
             let evaluated_ptr = arg.code_gen(env).unwrap();
             let var_id = env.get_tmp_var_id();
             let evaluated = env.builder.build_load(evaluated_ptr, &var_id);
+            // 可変長引数の部分は C と同じく bool を int に広げて渡す
+            let evaluated = match evaluated {
+                BasicValueEnum::IntValue(value)
+                    if i >= fixed_params && value.get_type().get_bit_width() == 1 =>
+                {
+                    env.builder
+                        .build_int_z_extend(value, env.ctx.i32_type(), "")
+                        .into()
+                }
+                _ => evaluated,
+            };
             evaluated_args.push(evaluated.into());
         }
 
-        let function = env.module.get_function(&self.id).unwrap();
         let var_id = env.get_tmp_var_id();
         Some(env.builder.build_call(function, &evaluated_args, &var_id))
     }
 }
 
-impl<'ll> CodeGen<'ll, VoidValue<'ll>> for IfElse<'ll> {
-    fn code_gen(self, env: &mut Env<'ll>) -> Option<VoidValue<'ll>> {
-        // generate cond, success, failure block
-        let ptr = self.cond.code_gen(env).unwrap();
-        let var_id = env.get_tmp_var_id();
-        let res = env.builder.build_load(ptr, &var_id).into_int_value();
+/// else if の連なりは全て同じ dest に合流させる
+/// dest に分岐するブランチがあったかどうかを返す
+fn if_else_chain_code_gen<'ll>(
+    if_else: IfElse<'ll>,
+    env: &mut Env<'ll>,
+    dest_block: BasicBlock<'ll>,
+) -> bool {
+    // generate cond, success, failure block
+    let ptr = if_else.cond.code_gen(env).unwrap();
+    let var_id = env.get_tmp_var_id();
+    let res = env.builder.build_load(ptr, &var_id).into_int_value();
 
-        // cond != 0
-        let zero = res.get_type().const_int(0, false);
-        let var_id = env.get_tmp_var_id();
-        let cond = env
-            .builder
-            .build_int_compare(inkwell::IntPredicate::NE, res, zero, &var_id);
+    // cond != 0
+    let zero = res.get_type().const_int(0, false);
+    let var_id = env.get_tmp_var_id();
+    let cond = env
+        .builder
+        .build_int_compare(inkwell::IntPredicate::NE, res, zero, &var_id);
 
-        // make success, failure, dest label
-        let success_label = env.get_tmp_label_id();
-        let fn_value = env.function_value.clone().unwrap();
-        let success_block = env.ctx.append_basic_block(fn_value, &success_label);
-        let tmp_label = env.get_tmp_label_id();
-        let failure_block = env.ctx.append_basic_block(fn_value, &tmp_label);
-        let label_id = env.get_tmp_label_id();
-        let dest_block = env.ctx.append_basic_block(fn_value, &label_id);
+    // make success, failure label
+    let success_label = env.get_tmp_label_id();
+    let fn_value = env.function_value.clone().unwrap();
+    let success_block = env.ctx.append_basic_block(fn_value, &success_label);
+    let tmp_label = env.get_tmp_label_id();
+    let failure_block = env.ctx.append_basic_block(fn_value, &tmp_label);
 
-        env.builder
-            .build_conditional_branch(cond, success_block, failure_block);
+    env.builder
+        .build_conditional_branch(cond, success_block, failure_block);
 
-        env.builder.position_at_end(success_block);
-        // then_block is always exists
-        self.success.code_gen(env);
+    env.builder.position_at_end(success_block);
+    // then_block is always exists
+    if_else.success.code_gen(env);
+    let success_reached = !env.is_terminated();
+    if success_reached {
         env.builder.build_unconditional_branch(dest_block);
+    }
 
-        env.builder.position_at_end(failure_block);
-        // else
-        if let Some(failure) = self.failure {
+    env.builder.position_at_end(failure_block);
+    // else
+    let failure_reached = match if_else.failure {
+        // else if
+        Some(mut failure) if failure.0.len() == 1 && matches!(failure.0[0], Stmt::IfElse(_)) => {
+            match failure.0.pop() {
+                Some(Stmt::IfElse(else_if)) => if_else_chain_code_gen(else_if, env, dest_block),
+                _ => unreachable!(),
+            }
+        }
+        Some(failure) => {
             failure.code_gen(env);
-        };
-        env.builder.build_unconditional_branch(dest_block);
+            let reached = !env.is_terminated();
+            if reached {
+                env.builder.build_unconditional_branch(dest_block);
+            }
+            reached
+        }
+        None => {
+            env.builder.build_unconditional_branch(dest_block);
+            true
+        }
+    };
+    success_reached || failure_reached
+}
 
+impl<'ll> CodeGen<'ll, VoidValue<'ll>> for IfElse<'ll> {
+    fn code_gen(self, env: &mut Env<'ll>) -> Option<VoidValue<'ll>> {
+        let fn_value = env.function_value.clone().unwrap();
+        let label_id = env.get_tmp_label_id();
+        let dest_block = env.ctx.append_basic_block(fn_value, &label_id);
+
+        let reached = if_else_chain_code_gen(self, env, dest_block);
+
+        // dest は最後のブロックにする
+        let last_block = fn_value.get_last_basic_block().unwrap();
+        if last_block != dest_block {
+            dest_block.move_after(last_block).unwrap();
+        }
         env.builder.position_at_end(dest_block);
+        // 全てのブランチが return などで抜けていたら, ここには来ない
+        if !reached {
+            env.builder.build_unreachable();
+        }
         None
     }
 }
 
 impl<'ll> CodeGen<'ll, VoidValue<'ll>> for Assign<'ll> {
     fn code_gen(self, env: &mut Env<'ll>) -> Option<IntValue<'ll>> {
+        // 左辺は変数か要素へのポインタになる. 式と同じく左から順に評価する
+        let ptr_left = self.left.code_gen(env).unwrap();
         let ptr_right = self.right.code_gen(env).unwrap();
         let tmp_id = env.get_tmp_var_id();
-        if let Some(ptr_left) = env.get_variable(self.left.clone()) {
-            env.builder.build_store(
-                ptr_left.clone(),
-                env.builder.build_load(ptr_right, &tmp_id).into_int_value(),
-            );
-        } else {
-            panic!("variable {} is not found.", self.left)
-        }
+        let value = env.builder.build_load(ptr_right, &tmp_id);
+        env.builder.build_store(ptr_left, value);
         None
     }
 }
@@ -312,6 +879,8 @@ impl<'ll> CodeGen<'ll, VoidValue<'ll>> for For<'ll> {
         let dest_id = env.get_tmp_label_id();
         let dest_block = env.ctx.append_basic_block(fn_value, &dest_id);
 
+        // 初期化した変数は for の中だけで見える
+        env.push_scope();
         self.var_decl.code_gen(env);
         env.builder.build_unconditional_branch(cond_block);
 
@@ -332,16 +901,25 @@ impl<'ll> CodeGen<'ll, VoidValue<'ll>> for For<'ll> {
             .build_conditional_branch(cond, do_block, dest_block);
 
         // generate do
+        // continue は update に飛ぶ
         env.builder.position_at_end(do_block);
+        env.loop_targets.push(LoopTarget {
+            continue_block: update_block,
+            break_block: dest_block,
+        });
         self.stmts.code_gen(env);
+        env.loop_targets.pop();
 
         // jmp update:
-        env.builder.build_unconditional_branch(update_block);
+        if !env.is_terminated() {
+            env.builder.build_unconditional_branch(update_block);
+        }
 
         // generate update
         env.builder.position_at_end(update_block);
         self.assign.code_gen(env);
         env.builder.build_unconditional_branch(cond_block);
+        env.pop_scope();
 
         // jmp dest:
         env.builder.position_at_end(dest_block);
@@ -349,6 +927,58 @@ impl<'ll> CodeGen<'ll, VoidValue<'ll>> for For<'ll> {
     }
 }
 
+impl<'ll> CodeGen<'ll, VoidValue<'ll>> for While<'ll> {
+    fn code_gen(self, env: &mut Env<'ll>) -> Option<IntValue<'ll>> {
+        //   jmp cond
+        // cond:
+        //   <cond>
+        //   jmp <do> or <dest>
+        // do:
+        //   <stmts>
+        //   jmp cond
+        // dest:
+        let fn_value = env.function_value.clone().unwrap();
+        let cond_label = env.get_tmp_label_id();
+        let cond_block = env.ctx.append_basic_block(fn_value, &cond_label);
+        let do_label = env.get_tmp_label_id();
+        let do_block = env.ctx.append_basic_block(fn_value, &do_label);
+        let dest_id = env.get_tmp_label_id();
+        let dest_block = env.ctx.append_basic_block(fn_value, &dest_id);
+
+        env.builder.build_unconditional_branch(cond_block);
+
+        // generate cond
+        env.builder.position_at_end(cond_block);
+        let ptr = self.cond.code_gen(env).unwrap();
+        let var_id = env.get_tmp_var_id();
+        let res = env.builder.build_load(ptr, &var_id).into_int_value();
+        // cond != 0
+        let zero = res.get_type().const_int(0, false);
+        let var_id = env.get_tmp_var_id();
+        let cond = env
+            .builder
+            .build_int_compare(inkwell::IntPredicate::NE, res, zero, &var_id);
+        env.builder
+            .build_conditional_branch(cond, do_block, dest_block);
+
+        // generate do
+        // continue は cond に飛ぶ
+        env.builder.position_at_end(do_block);
+        env.loop_targets.push(LoopTarget {
+            continue_block: cond_block,
+            break_block: dest_block,
+        });
+        self.stmts.code_gen(env);
+        env.loop_targets.pop();
+        if !env.is_terminated() {
+            env.builder.build_unconditional_branch(cond_block);
+        }
+
+        env.builder.position_at_end(dest_block);
+        None
+    }
+}
+
 impl<'ll> CodeGen<'ll, VoidValue<'ll>> for Stmt<'ll> {
     fn code_gen(self, env: &mut Env<'ll>) -> Option<VoidValue<'ll>> {
         match self {
@@ -377,6 +1007,21 @@ impl<'ll> CodeGen<'ll, VoidValue<'ll>> for Stmt<'ll> {
             Stmt::For(for_) => {
                 Some(for_.code_gen(env));
             }
+            Stmt::While(while_) => {
+                Some(while_.code_gen(env));
+            }
+            Stmt::Match(m) => {
+                m.code_gen(env);
+            }
+            Stmt::Break(_) => {
+                let target = env.loop_targets.last().expect("break is out of loop");
+                env.builder.build_unconditional_branch(target.break_block);
+            }
+            Stmt::Continue(_) => {
+                let target = env.loop_targets.last().expect("continue is out of loop");
+                env.builder
+                    .build_unconditional_branch(target.continue_block);
+            }
         };
         None
     }
@@ -384,48 +1029,111 @@ impl<'ll> CodeGen<'ll, VoidValue<'ll>> for Stmt<'ll> {
 
 impl<'ll> CodeGen<'ll, VoidValue<'ll>> for Stmts<'ll> {
     fn code_gen(self, env: &mut Env<'ll>) -> Option<VoidValue<'ll>> {
+        env.push_scope();
         for stmt in self.0 {
+            // return, break, continue より後ろは実行されないので作らない
+            if env.is_terminated() {
+                break;
+            }
             stmt.code_gen(env);
         }
+        env.pop_scope();
         None
     }
 }
 
-impl<'ll> CodeGen<'ll, IntValue<'ll>> for FunctionDecl<'ll> {
-    fn code_gen(self, env: &mut Env<'ll>) -> Option<IntValue<'ll>> {
-        let llvm_ret_typ = env.get_llvm_fn_type(self.ret_typ.clone());
+impl<'ll> FunctionDecl<'ll> {
+    /// 関数をモジュールに追加する. 本体は `code_gen` で作る
+    fn declare(&self, env: &mut Env<'ll>) {
+        let params: Vec<Type> = self.args.iter().map(|arg| arg.ty.clone()).collect();
+        let fn_value = env.declare_function(&self.id, &params, &self.ret_typ, false);
+        if env.functions.insert(self.id.clone(), fn_value).is_some() {
+            panic!("function {} is already decleared", &self.id);
+        }
+    }
+}
 
-        let fn_type: FunctionType = if let Some(llvm_ret_type) = llvm_ret_typ {
-            llvm_ret_type.fn_type(
-                &self
-                    .args
-                    .iter()
-                    .map(|arg| env.get_llvm_value_type(arg.ty.clone()))
-                    .collect::<Vec<BasicMetadataTypeEnum>>(),
-                false,
-            )
-        } else {
-            env.ctx.void_type().fn_type(
-                &self
-                    .args
-                    .iter()
-                    .map(|arg| env.get_llvm_value_type(arg.ty.clone()))
-                    .collect::<Vec<BasicMetadataTypeEnum>>(),
-                false,
-            )
-        };
+impl<'ll> ExternFn<'ll> {
+    /// C の関数を宣言する. 本体はリンク時に探す
+    fn declare(&self, env: &mut Env<'ll>) {
+        let params: Vec<Type> = self.args.iter().map(|arg| arg.ty.clone()).collect();
+        let fn_value = env.declare_function(&self.id, &params, &self.ret_typ, self.variadic);
+        // C の呼び出し規約 (LLVM の ccc)
+        fn_value.set_call_conventions(0);
+        if env.functions.insert(self.id.clone(), fn_value).is_some() {
+            panic!("function {} is already decleared", &self.id);
+        }
+    }
+}
 
-        let fn_value = env.module.add_function(&self.id, fn_type, None);
+impl<'ll> StructDecl<'ll> {
+    /// 名前だけの構造体型を作る. 互いに参照できるように中身は `define` で後から決める
+    fn declare(&self, env: &mut Env<'ll>) {
+        let struct_type = env.ctx.opaque_struct_type(&self.id);
+        let names = self.fields.iter().map(|field| field.id.clone()).collect();
+        env.structs.insert(self.id.clone(), (struct_type, names));
+    }
+
+    fn define(&self, env: &mut Env<'ll>) {
+        let field_types: Vec<BasicTypeEnum> = self
+            .fields
+            .iter()
+            .map(|field| env.get_llvm_type(&field.ty))
+            .collect();
+        env.structs[&self.id].0.set_body(&field_types, false);
+    }
+}
+
+impl<'ll> EnumDecl<'ll> {
+    /// 名前だけの構造体型を作る. 中身は `define` で後から決める
+    fn declare(&self, env: &mut Env<'ll>) {
+        let enum_type = env.ctx.opaque_struct_type(&self.id);
+        // 0 番目は discriminant
+        let mut next = 1;
+        let variants = self
+            .variants
+            .iter()
+            .map(|variant| {
+                let first = next;
+                next += variant.fields.len() as u32;
+                (variant.id.clone(), first)
+            })
+            .collect();
+        env.enums.insert(self.id.clone(), (enum_type, variants));
+    }
+
+    /// `{ i32, 全てのバリアントの値 }` にする
+    fn define(&self, env: &mut Env<'ll>) {
+        let mut field_types: Vec<BasicTypeEnum> = vec![env.ctx.i32_type().into()];
+        for variant in self.variants.iter() {
+            field_types.extend(variant.fields.iter().map(|ty| env.get_llvm_type(ty)));
+        }
+        env.enums[&self.id].0.set_body(&field_types, false);
+    }
+}
+
+impl<'ll> GlobalDecl<'ll> {
+    /// 型検査で計算した値で初期化した global を作る. 変数と同じくポインタで読み書きする
+    fn define(&self, env: &mut Env<'ll>) {
+        let int_type = env.get_llvm_int_type(&self.ty);
+        let global = env.module.add_global(int_type, None, &self.id);
+        global.set_initializer(&int_type.const_int(self.value as u64, self.ty.is_signed()));
+        // const は書き換えられない
+        global.set_constant(!self.mutable);
+        env.set_variable(self.id.clone(), global.as_pointer_value());
+    }
+}
+
+impl<'ll> CodeGen<'ll, IntValue<'ll>> for FunctionDecl<'ll> {
+    fn code_gen(self, env: &mut Env<'ll>) -> Option<IntValue<'ll>> {
+        let llvm_ret_typ = env.get_llvm_fn_type(&self.ret_typ);
+        let fn_value = env.functions[&self.id];
 
         // 現在の関数の情報を設定
         env.function_value = Some(fn_value.clone());
         env.function = self.id.clone();
-        dbg!(&env.functions);
-        if env.functions.insert(self.id.clone(), fn_value).is_some() {
-            panic!("function {} is already decleared", &self.id);
-        }
-        // 変数マップを作る
-        env.variables.entry(self.id.clone()).or_default();
+        // 引数のスコープ
+        env.push_scope();
 
         // block
         // TODO: main() だけでいいのか？
@@ -434,20 +1142,25 @@ impl<'ll> CodeGen<'ll, IntValue<'ll>> for FunctionDecl<'ll> {
 
         // 引数を使う時
         for (i, arg) in self.args.iter().enumerate() {
-            let param = fn_value.get_nth_param(i as u32).unwrap().into_int_value();
-
-            // TODO: type check
+            let param = fn_value.get_nth_param(i as u32).unwrap();
 
             // 引数名に対応するptrを作成
-            let ptr_param = env.int_to_point(param, Some(&arg.id.clone()));
+            let ptr_param = env
+                .builder
+                .build_alloca(env.get_llvm_type(&arg.ty), &arg.id);
+            env.builder.build_store(ptr_param, param);
             env.set_variable(arg.id.clone(), ptr_param);
         }
 
         self.stmts.code_gen(env);
+        env.pop_scope();
 
         // returnがないときも0をかえすようにしている
-        if llvm_ret_typ.is_none() {
+        if llvm_ret_typ.is_none() && !env.is_terminated() {
             env.builder.build_return(None);
+        } else if !env.is_terminated() {
+            // 値を返す関数の終わりに来ないことは型検査で確かめている. 無限ループの後など
+            env.builder.build_unreachable();
         }
 
         env.function_value = None;
@@ -458,9 +1171,738 @@ impl<'ll> CodeGen<'ll, IntValue<'ll>> for FunctionDecl<'ll> {
 
 impl<'ll> CodeGen<'ll, IntValue<'ll>> for Program<'ll> {
     fn code_gen(self, env: &mut Env<'ll>) -> Option<IntValue<'ll>> {
-        for function in self.0 {
-            function.code_gen(env);
+        // 構造体と enum の型は関数の宣言で使うので先に作る
+        for decl in self.structs() {
+            decl.declare(env);
+        }
+        for decl in self.enums() {
+            decl.declare(env);
+        }
+        for decl in self.structs() {
+            decl.define(env);
+        }
+        for decl in self.enums() {
+            decl.define(env);
         }
+        // 先に全ての関数を宣言しておくと, 定義順に関係なく呼べる
+        for item in self.0.iter() {
+            match item {
+                Item::Function(function) => function.declare(env),
+                Item::ExternFn(decl) => decl.declare(env),
+                Item::Struct(_) | Item::Enum(_) | Item::Global(_) => {}
+            }
+        }
+        // const と static は全ての関数から見える一番外側のスコープに置く
+        env.push_scope();
+        for decl in self.globals() {
+            decl.define(env);
+        }
+        for item in self.0 {
+            if let Item::Function(function) = item {
+                function.code_gen(env);
+            }
+        }
+        env.pop_scope();
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    use inkwell::targets::{InitializationConfig, Target};
+    use inkwell::OptimizationLevel;
+    use ipulang_parser::ast::program_parser;
+    use ipulang_typecheck::type_check::type_check;
+
+    thread_local! {
+        /// putchar で出力された文字
+        static OUTPUT: RefCell<String> = RefCell::new(String::new());
+        /// getchar で読む文字
+        static INPUT: RefCell<Vec<u8>> = RefCell::new(vec![]);
+    }
+
+    extern "C" fn putchar_mock(c: i32) -> i32 {
+        OUTPUT.with(|output| output.borrow_mut().push(c as u8 as char));
+        c
+    }
+
+    extern "C" fn getchar_mock() -> i32 {
+        INPUT.with(|input| {
+            let mut input = input.borrow_mut();
+            if input.is_empty() {
+                -1
+            } else {
+                input.remove(0) as i32
+            }
+        })
+    }
+
+    fn compile<'ll>(context: &'ll Context, code: &'ll str) -> Env<'ll> {
+        let program = program_parser(Span::new(code)).unwrap();
+        let program = type_check(program).unwrap();
+
+        let mut env = Env::new(context);
+        program.code_gen(&mut env);
+        env
+    }
+
+    /// main を JIT で実行して, 返り値と putchar の出力を返す
+    fn run(code: &str) -> (i32, String) {
+        run_with_input(code, "")
+    }
+
+    /// `input` を getchar で読めるようにして実行する
+    fn run_with_input(code: &str, input: &str) -> (i32, String) {
+        let context = Context::create();
+        let env = compile(&context, code);
+
+        Target::initialize_native(&InitializationConfig::default()).unwrap();
+        let engine = env
+            .module
+            .create_jit_execution_engine(OptimizationLevel::None)
+            .unwrap();
+        engine.add_global_mapping(&env.functions["putchar"], putchar_mock as usize);
+        engine.add_global_mapping(&env.functions["getchar"], getchar_mock as usize);
+
+        OUTPUT.with(|output| output.borrow_mut().clear());
+        INPUT.with(|i| *i.borrow_mut() = input.as_bytes().to_vec());
+        let ret = unsafe {
+            engine
+                .get_function::<unsafe extern "C" fn() -> i32>("main")
+                .unwrap()
+                .call()
+        };
+        (ret, OUTPUT.with(|output| output.borrow().clone()))
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let code = r#"
+fn t(c: i32): bool {
+    putchar(c);
+    return 1_bool;
+}
+
+fn f(c: i32): bool {
+    putchar(c);
+    return 0_bool;
+}
+
+fn main(): i32 {
+    if (f(65) && t(66)) { putchar(67); }
+    if (t(68) || f(69)) { putchar(70); }
+    if (t(71) && f(72)) { putchar(73); }
+    if (f(74) || t(75)) { putchar(76); }
+    if (f(77) || f(78) && t(79)) { putchar(80); }
+    return 0;
+}
+"#;
+        assert_eq!(run(code), (0, "ADFGHJKLMN".to_owned()));
+    }
+
+    #[test]
+    fn test_short_circuit_guard() {
+        let code = r#"
+fn main(): i32 {
+    var i: i32 = 0;
+    var a: i32 = 0;
+    if (i != 0 && 10 / i > 1) { a = 1; }
+    if (i == 0 || 10 / i > 1) { a = a + 2; }
+    return a;
+}
+"#;
+        assert_eq!(run(code).0, 2);
+    }
+
+    #[test]
+    fn test_while_break_continue() {
+        let code = r#"
+fn main(): i32 {
+    var sum: i32 = 0;
+    var i: i32 = 0;
+    while (1_bool) {
+        i = i + 1;
+        if (i > 10) { break; }
+        if (i % 2 == 0) { continue; }
+        sum = sum + i;
+    }
+    for (var j: i32 = 0; j < 10; j = j + 1;) {
+        if (j == 3) { continue; }
+        if (j == 6) { break; }
+        putchar(48 + j);
+    }
+    return sum;
+}
+"#;
+        assert_eq!(run(code), (25, "01245".to_owned()));
+    }
+
+    #[test]
+    fn test_else_if() {
+        let code = r#"
+fn classify(i: i32): i32 {
+    var res: i32 = 0;
+    if (i % 15 == 0) {
+        res = 3;
+    } else if (i % 5 == 0) {
+        res = 2;
+    } else if (i % 3 == 0) {
+        res = 1;
+    } else {
+        res = 0;
+    }
+    return res;
+}
+
+fn main(): i32 {
+    for (var i: i32 = 1; i < 16; i = i + 1;) {
+        putchar(48 + classify(i));
+    }
+    return 0;
+}
+"#;
+        assert_eq!(run(code), (0, "001021001201003".to_owned()));
+
+        // entry, 各 if の success と failure, 合流先が 1 つ
+        let context = Context::create();
+        let env = compile(&context, code);
+        let classify = env.module.get_function("classify").unwrap();
+        assert_eq!(classify.count_basic_blocks(), 1 + 3 * 2 + 1);
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let code = r#"
+fn main(): i32 {
+    var a: i32 = 1;
+    if (1_bool) {
+        var a: i32 = 2;
+        putchar(48 + a);
+    }
+    putchar(48 + a);
+    for (var i: i32 = 0; i < 2; i = i + 1;) {
+        var a: i32 = a + 5;
+        putchar(48 + a);
+    }
+    return a;
+}
+"#;
+        assert_eq!(run(code), (1, "2166".to_owned()));
+    }
+
+    #[test]
+    fn test_mutual_recursion() {
+        let code = r#"
+fn main(): i32 {
+    putchar(48 + toint(even(10)));
+    putchar(48 + toint(odd(7)));
+    return toint(even(3));
+}
+fn even(n: i32): bool {
+    if (n == 0) {
+        return 1_bool;
+    }
+    return odd(n - 1);
+}
+fn odd(n: i32): bool {
+    if (n == 0) {
+        return 0_bool;
+    }
+    return even(n - 1);
+}
+fn toint(b: bool): i32 {
+    if (b) {
+        return 1;
+    }
+    return 0;
+}
+"#;
+        assert_eq!(run(code), (0, "11".to_owned()));
+    }
+
+    #[test]
+    fn test_dead_code() {
+        let code = r#"
+fn fib(i: i32): i32 {
+    if (i <= 1) {
+        return 1;
+    } else {
+        return fib(i - 1) + fib(i - 2);
+    }
+}
+fn main(): i32 {
+    while (1_bool) {
+        putchar(65);
+        break;
+        putchar(66);
+    }
+    return fib(5) + forever(3);
+    putchar(67);
+}
+fn forever(i: i32): i32 {
+    while (1_bool) {
+        if (i == 0) {
+            return 1;
+        }
+        i = i - 1;
+    }
+}
+"#;
+        assert_eq!(run(code), (9, "A".to_owned()));
+    }
+
+    #[test]
+    fn test_unary() {
+        let code = r#"
+fn main(): i32 {
+    var a: i32 = 5;
+    if (-a == 0 - 5) {
+        putchar(65);
+    }
+    var b: i64 = -4294967296_i64;
+    if (-b == 4294967296_i64) {
+        putchar(66);
+    }
+    if (~a == -6) {
+        putchar(67);
+    }
+    var c: u32 = 0_u32;
+    if (~c == 4294967295_u32) {
+        putchar(68);
+    }
+    var d: u64 = 15_u64;
+    if (~d == 18446744073709551600_u64) {
+        putchar(69);
+    }
+    var e: bool = a < 0;
+    if (!e) {
+        putchar(70);
+    }
+    if (!!e) {
+        putchar(71);
+    }
+    return -(-a);
+}
+"#;
+        assert_eq!(run(code), (5, "ABCDEF".to_owned()));
+    }
+
+    #[test]
+    fn test_unsigned() {
+        let code = r#"
+fn main(): i32 {
+    var a: u32 = 4000000000_u32;
+    var b: u32 = 3_u32;
+    if (a > b) {
+        putchar(65);
+    }
+    if (a / b == 1333333333_u32) {
+        putchar(66);
+    }
+    if (a % b == 1_u32) {
+        putchar(67);
+    }
+    if (a >> 31 == 1_u32) {
+        putchar(68);
+    }
+    var c: i32 = -8;
+    if (c >> 1_u64 == -4) {
+        putchar(69);
+    }
+    var d: u64 = 1_u64 << 40;
+    if (d >> 8_u32 == 4294967296_u64) {
+        putchar(70);
+    }
+    var e: u64;
+    return 0;
+}
+"#;
+        assert_eq!(run(code), (0, "ABCDEF".to_owned()));
+    }
+
+    #[test]
+    fn test_cast() {
+        let code = r#"
+fn main(): i32 {
+    var a: i32 = -1;
+    var b: i64 = a as i64;
+    if (b == -1_i64) {
+        putchar(65);
+    }
+    var c: u64 = a as u32 as u64;
+    if (c == 4294967295_u64) {
+        putchar(66);
+    }
+    if (4294967297_i64 as i32 == 1) {
+        putchar(67);
+    }
+    var d: u64 = (1 < 2) as u64 + (2 < 1) as u64;
+    return d as i32 + 41;
+}
+"#;
+        assert_eq!(run(code), (42, "ABC".to_owned()));
+    }
+
+    #[test]
+    fn test_infer() {
+        let code = r#"
+fn main(): i32 {
+    var a = 4000000000_u32;
+    var b: i64 = 5;
+    var c = a / 2 + 1;
+    if (c > 1999999999) {
+        putchar(65);
+    }
+    var d = b << 33;
+    return (d >> 32) as i32;
+}
+"#;
+        assert_eq!(run(code), (10, "A".to_owned()));
+    }
+
+    #[test]
+    fn test_int_literal() {
+        let code = r#"
+fn main(): i32 {
+    var a: i64 = 3000000000;
+    if (a + 1_000_000_000 == 4000000000) {
+        putchar(65);
+    }
+    var b: u32 = 0xffff_ffff;
+    if (b == 4294967295) {
+        putchar(66);
+    }
+    var c: u64 = -1 as u64;
+    if (c == 0xffff_ffff_ffff_ffff) {
+        putchar(67);
+    }
+    return 0b10_1010;
+}
+"#;
+        assert_eq!(run(code), (42, "ABC".to_owned()));
+    }
+
+    #[test]
+    fn test_string_literal() {
+        let code = r#"
+fn id(s: string): string {
+    return s;
+}
+
+fn main(): i32 {
+    var a = id("hi\n");
+    var b: string;
+    b = "hi\n";
+    var c = "say \"\x41\"";
+    putchar('O');
+    putchar('\x4b');
+    return 0;
+}
+"#;
+        let context = Context::create();
+        let env = compile(&context, code);
+        let ir = env.module.print_to_string().to_string();
+        // 同じ文字列は 1 つの定数にまとまる
+        assert_eq!(ir.matches(r#"c"hi\0A\00""#).count(), 1, "{}", ir);
+        assert_eq!(
+            ir.matches("private unnamed_addr constant").count(),
+            3,
+            "{}",
+            ir
+        );
+        assert!(ir.contains(r#"c"say \22A\22\00""#), "{}", ir);
+        assert!(env.module.verify().is_ok());
+
+        assert_eq!(run(code), (0, "OK".to_owned()));
+    }
+
+    #[test]
+    fn test_extern_fn() {
+        let code = r#"
+extern fn abs(x: i32): i32;
+extern fn printf(format: string, ...): i32;
+fn main(): i32 {
+    printf("%d %d\n", abs(-3), 1 < 2);
+    return abs(-3);
+}
+"#;
+        let context = Context::create();
+        let env = compile(&context, code);
+        let ir = env.module.print_to_string().to_string();
+        assert!(ir.contains("declare i32 @abs(i32)"), "{}", ir);
+        assert!(ir.contains("declare i32 @printf(i8*, ...)"), "{}", ir);
+        // bool は i32 に広げて渡す
+        assert!(ir.contains("zext i1"), "{}", ir);
+        assert!(env.module.verify().is_ok());
+    }
+
+    #[test]
+    fn test_builtins() {
+        let code = r#"
+fn main(): i32 {
+    print_i32(-123);
+    print_str(" ");
+    print_i32(0);
+    println();
+    print_i64(-9223372036854775807_i64 - 1);
+    println();
+    print_bool(1 < 2);
+    print_bool(2 < 1);
+    println();
+    var a = read_i32();
+    var b = read_i32();
+    print_i32(a + b);
+    return read_i32();
+}
+"#;
+        let expected = "-123 0\n-9223372036854775808\ntruefalse\n-25";
+        assert_eq!(
+            run_with_input(code, "  -42\n17 x"),
+            (0, expected.to_owned())
+        );
+    }
+
+    #[test]
+    fn test_array() {
+        let code = r#"
+fn sum(a: [i64; 4]): i64 {
+    var s: i64 = 0;
+    for (var i: i32 = 0; i < 4; i = i + 1;) {
+        s = s + a[i];
+    }
+    return s;
+}
+
+fn main(): i32 {
+    var a = [1_i64, 2, 3, 4];
+    a[3] = 10;
+    print_i64(sum(a));
+    var b: [[i32; 2]; 2];
+    b[1][0] = 7;
+    var c = b;
+    c[1][0] = 8;
+    print_i32(b[1][0] + b[0][1]);
+    print_i32(c[1][0]);
+    return [5, 6, 7][2];
+}
+"#;
+        assert_eq!(run(code), (7, "1678".to_owned()));
+    }
+
+    #[test]
+    fn test_pointer() {
+        let code = r#"
+fn swap(a: *i32, b: *i32): unit {
+    var t = *a;
+    *a = *b;
+    *b = t;
+}
+
+fn main(): i32 {
+    var x = 1;
+    var y = 2;
+    swap(&x, &y);
+    print_i32(x * 10 + y);
+    var p = &x;
+    var pp = &p;
+    **pp = *p + 5;
+    print_i32(x);
+    var a = [1, 2, 3];
+    var q = &a;
+    (*q)[0] = 4;
+    var r = &a[2];
+    *r = *r * 3;
+    print_i32(a[0] + a[2]);
+    return *p;
+}
+"#;
+        assert_eq!(run(code), (7, "21713".to_owned()));
+    }
+
+    #[test]
+    fn test_assign_order() {
+        let code = r#"
+fn f(p: *i32): *i32 {
+    putchar(65);
+    return p;
+}
+fn g(): i32 {
+    putchar(66);
+    return 7;
+}
+fn h(i: i32): i32 {
+    putchar(67);
+    return i;
+}
+fn main(): i32 {
+    var a = 0;
+    *f(&a) = g();
+    var b: [i32; 2] = [0, 0];
+    b[h(1)] = g() + h(0);
+    return a + b[1];
+}
+"#;
+        assert_eq!(run(code), (14, "ABCBC".to_owned()));
+    }
+
+    #[test]
+    fn test_struct() {
+        let code = r#"
+struct Point { x: i32, y: i32 }
+struct Rect { min: Point, max: Point }
+
+fn add(a: Point, b: Point): Point {
+    return Point { x: a.x + b.x, y: a.y + b.y };
+}
+
+fn area(r: *Rect): i32 {
+    return ((*r).max.x - (*r).min.x) * ((*r).max.y - (*r).min.y);
+}
+
+fn main(): i32 {
+    var p = Point { y: 2, x: 1 };
+    var q = add(p, Point { x: 10, y: 20 });
+    print_i32(q.x);
+    print_str(",");
+    print_i32(q.y);
+    println();
+    var r: Rect;
+    r.max = q;
+    r.min.y = 2;
+    print_i32(area(&r));
+    println();
+    p.x = 5;
+    return p.x + p.y;
+}
+"#;
+        assert_eq!(run(code), (7, "11,22\n220\n".to_owned()));
+
+        let context = Context::create();
+        let env = compile(&context, code);
+        let ir = env.module.print_to_string().to_string();
+        assert!(ir.contains("%Point = type { i32, i32 }"), "{}", ir);
+        assert!(ir.contains("%Rect = type { %Point, %Point }"), "{}", ir);
+        assert!(env.module.verify().is_ok());
+    }
+
+    #[test]
+    fn test_enum() {
+        let code = r#"
+enum Shape { Circle(i32), Rect(i32, i32), Empty }
+enum Color { Red, Green, Blue }
+
+fn area(s: Shape): i32 {
+    match (s) {
+        Shape::Circle(r) => { return 3 * r * r; }
+        Shape::Rect(w, h) => { return w * h; }
+        Shape::Empty => { return 0; }
+    }
+}
+
+fn code(c: Color): i32 {
+    return match (c) { Color::Red => 1, Color::Blue => 3, _ => 2 };
+}
+
+fn main(): i32 {
+    var shapes = [Shape::Circle(2), Shape::Rect(3, 4), Shape::Empty];
+    for (var i: i32 = 0; i < 3; i = i + 1;) {
+        print_i32(area(shapes[i]));
+        println();
+    }
+    match (shapes[1]) {
+        Shape::Rect(w, _) => print_i32(w),
+        _ => {}
+    }
+    println();
+    return code(Color::Red) + code(Color::Green) * 10 + code(Color::Blue) * 100;
+}
+"#;
+        assert_eq!(run(code), (321, "12\n12\n0\n3\n".to_owned()));
+
+        let context = Context::create();
+        let env = compile(&context, code);
+        let ir = env.module.print_to_string().to_string();
+        assert!(
+            ir.contains("%Shape = type { i32, i32, i32, i32 }"),
+            "{}",
+            ir
+        );
+        assert!(ir.contains("%Color = type { i32 }"), "{}", ir);
+        assert!(ir.contains("switch i32"), "{}", ir);
+        assert!(env.module.verify().is_ok());
+    }
+
+    #[test]
+    fn test_global() {
+        let code = r#"
+const N: i32 = 4;
+const BASE: i64 = 1 << 40;
+static mut counter: i32 = 0;
+
+fn next(): i32 {
+    counter = counter + 1;
+    return counter;
+}
+
+fn main(): i32 {
+    var a: [i32; N];
+    for (var i = 0; i < N; i = i + 1;) {
+        a[i] = next() * 10;
+    }
+    print_i32(a[N - 1]);
+    println();
+    print_i64(BASE + 1_i64);
+    println();
+    var counter = 100;
+    return counter + next();
+}
+"#;
+        assert_eq!(run(code), (105, "40\n1099511627777\n".to_owned()));
+
+        let context = Context::create();
+        let env = compile(&context, code);
+        let ir = env.module.print_to_string().to_string();
+        assert!(ir.contains("@N = constant i32 4"), "{}", ir);
+        assert!(ir.contains("@BASE = constant i64 1099511627776"), "{}", ir);
+        assert!(ir.contains("@counter = global i32 0"), "{}", ir);
+        assert!(ir.contains("alloca [4 x i32]"), "{}", ir);
+        assert!(env.module.verify().is_ok());
+    }
+
+    #[test]
+    fn test_bounds_check() {
+        let code = r#"
+fn get(a: [i32; 3], i: i32): i32 {
+    return a[i];
+}
+fn main(): i32 {
+    return get([1, 2, 3], 3);
+}
+"#;
+        let context = Context::create();
+        let env = compile(&context, code);
+        let ir = env.module.print_to_string().to_string();
+        assert!(
+            ir.contains("index out of bounds at line 3: the length is 3 but the index is "),
+            "{}",
+            ir
+        );
+        assert!(ir.contains("call void @abort()"), "{}", ir);
+        assert!(env.module.verify().is_ok());
+
+        // 外すと比較も abort も作らない
+        let program = program_parser(Span::new(code)).unwrap();
+        let program = type_check(program).unwrap();
+        let context = Context::create();
+        let mut env = Env::new(&context);
+        env.bounds_check = false;
+        program.code_gen(&mut env);
+        let ir = env.module.print_to_string().to_string();
+        assert!(!ir.contains("index out of bounds"), "{}", ir);
+        assert!(!ir.contains("call void @abort()"), "{}", ir);
+        assert!(env.module.verify().is_ok());
+    }
+}
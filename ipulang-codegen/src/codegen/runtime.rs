@@ -0,0 +1,265 @@
+//! 組み込み関数のうちランタイムの本体. libc の putchar, getchar だけを使って LLVM IR で書く
+use inkwell::module::Linkage;
+use inkwell::values::*;
+use inkwell::IntPredicate;
+
+use super::context::Env;
+
+/// `ipulang_parser::prelude::PRELUDE` のうち libc にない関数の本体を作る
+/// 宣言は `Env::declare_builtins` で済ませてある
+pub fn define_runtime(env: &mut Env) {
+    let print_u64 = define_print_u64(env);
+    define_print_i64(env, print_u64);
+    define_print_i32(env);
+    define_print_str(env);
+    define_print_bool(env);
+    define_println(env);
+    define_read_i32(env);
+}
+
+/// 本体を作り始める. モジュールの外からは見えないようにする
+fn begin<'ll>(env: &mut Env<'ll>, name: &str) -> FunctionValue<'ll> {
+    let function = env.functions[name];
+    function.set_linkage(Linkage::Internal);
+    let entry = env.ctx.append_basic_block(function, "entry");
+    env.builder.position_at_end(entry);
+    function
+}
+
+fn call<'ll>(
+    env: &Env<'ll>,
+    name: &str,
+    args: &[BasicMetadataValueEnum<'ll>],
+) -> CallSiteValue<'ll> {
+    env.builder.build_call(env.functions[name], args, "")
+}
+
+fn putchar<'ll>(env: &Env<'ll>, c: IntValue<'ll>) {
+    call(env, "putchar", &[c.into()]);
+}
+
+fn getchar<'ll>(env: &Env<'ll>) -> IntValue<'ll> {
+    call(env, "getchar", &[])
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_int_value()
+}
+
+/// 符号なしで10進数を出力する. 上の桁から出すために再帰する
+fn define_print_u64<'ll>(env: &mut Env<'ll>) -> FunctionValue<'ll> {
+    // entry:
+    //   br (n >= 10) upper, digit
+    // upper:
+    //   print_u64(n / 10)
+    // digit:
+    //   putchar('0' + n % 10)
+    let i64_type = env.ctx.i64_type();
+    let fn_type = env.ctx.void_type().fn_type(&[i64_type.into()], false);
+    let function = env
+        .module
+        .add_function("ipulang.print_u64", fn_type, Some(Linkage::Internal));
+    let entry = env.ctx.append_basic_block(function, "entry");
+    let upper = env.ctx.append_basic_block(function, "upper");
+    let digit = env.ctx.append_basic_block(function, "digit");
+
+    env.builder.position_at_end(entry);
+    let n = function.get_nth_param(0).unwrap().into_int_value();
+    let ten = i64_type.const_int(10, false);
+    let has_upper = env.builder.build_int_compare(IntPredicate::UGE, n, ten, "");
+    env.builder
+        .build_conditional_branch(has_upper, upper, digit);
+
+    env.builder.position_at_end(upper);
+    let rest = env.builder.build_int_unsigned_div(n, ten, "");
+    env.builder.build_call(function, &[rest.into()], "");
+    env.builder.build_unconditional_branch(digit);
+
+    env.builder.position_at_end(digit);
+    let d = env.builder.build_int_unsigned_rem(n, ten, "");
+    let d = env.builder.build_int_truncate(d, env.ctx.i32_type(), "");
+    let c = env
+        .builder
+        .build_int_add(d, env.ctx.i32_type().const_int('0' as u64, false), "");
+    putchar(env, c);
+    env.builder.build_return(None);
+    function
+}
+
+fn define_print_i64<'ll>(env: &mut Env<'ll>, print_u64: FunctionValue<'ll>) {
+    // 絶対値は符号なしとして出すので i64::MIN も出せる
+    let function = begin(env, "print_i64");
+    let minus = env.ctx.append_basic_block(function, "minus");
+    let body = env.ctx.append_basic_block(function, "body");
+
+    let n = function.get_nth_param(0).unwrap().into_int_value();
+    let zero = n.get_type().const_zero();
+    let negative = env
+        .builder
+        .build_int_compare(IntPredicate::SLT, n, zero, "");
+    let neg = env.builder.build_int_sub(zero, n, "");
+    let abs = env.builder.build_select(negative, neg, n, "");
+    env.builder.build_conditional_branch(negative, minus, body);
+
+    env.builder.position_at_end(minus);
+    putchar(env, env.ctx.i32_type().const_int('-' as u64, false));
+    env.builder.build_unconditional_branch(body);
+
+    env.builder.position_at_end(body);
+    env.builder.build_call(print_u64, &[abs.into()], "");
+    env.builder.build_return(None);
+}
+
+fn define_print_i32(env: &mut Env) {
+    let function = begin(env, "print_i32");
+    let n = function.get_nth_param(0).unwrap().into_int_value();
+    let n = env.builder.build_int_s_extend(n, env.ctx.i64_type(), "");
+    call(env, "print_i64", &[n.into()]);
+    env.builder.build_return(None);
+}
+
+/// NUL まで1文字ずつ出力する
+fn define_print_str(env: &mut Env) {
+    // entry:
+    //   br loop
+    // loop:
+    //   p = phi [s, entry], [next, body]
+    //   br (*p == 0) exit, body
+    // body:
+    //   putchar(*p)
+    //   next = p + 1
+    //   br loop
+    // exit:
+    let function = begin(env, "print_str");
+    let entry = env.builder.get_insert_block().unwrap();
+    let loop_block = env.ctx.append_basic_block(function, "loop");
+    let body = env.ctx.append_basic_block(function, "body");
+    let exit = env.ctx.append_basic_block(function, "exit");
+
+    let s = function.get_nth_param(0).unwrap().into_pointer_value();
+    env.builder.build_unconditional_branch(loop_block);
+
+    env.builder.position_at_end(loop_block);
+    let p = env.builder.build_phi(s.get_type(), "p");
+    let p_value = p.as_basic_value().into_pointer_value();
+    let c = env.builder.build_load(p_value, "").into_int_value();
+    let is_end = env
+        .builder
+        .build_int_compare(IntPredicate::EQ, c, c.get_type().const_zero(), "");
+    env.builder.build_conditional_branch(is_end, exit, body);
+
+    env.builder.position_at_end(body);
+    let c = env.builder.build_int_z_extend(c, env.ctx.i32_type(), "");
+    putchar(env, c);
+    let one = env.ctx.i64_type().const_int(1, false);
+    let next = unsafe { env.builder.build_in_bounds_gep(p_value, &[one], "") };
+    env.builder.build_unconditional_branch(loop_block);
+    p.add_incoming(&[(&s, entry), (&next, body)]);
+
+    env.builder.position_at_end(exit);
+    env.builder.build_return(None);
+}
+
+fn define_print_bool(env: &mut Env) {
+    let function = begin(env, "print_bool");
+    let b = function.get_nth_param(0).unwrap().into_int_value();
+    let true_str = env.get_string("true");
+    let false_str = env.get_string("false");
+    let s = env.builder.build_select(b, true_str, false_str, "");
+    call(env, "print_str", &[s.into()]);
+    env.builder.build_return(None);
+}
+
+fn define_println(env: &mut Env) {
+    begin(env, "println");
+    putchar(env, env.ctx.i32_type().const_int('\n' as u64, false));
+    env.builder.build_return(None);
+}
+
+/// 空白を読み飛ばし, `-` と数字を読む. 数字でない文字を読んだところで終わる
+fn define_read_i32(env: &mut Env) {
+    // entry:
+    //   br skip
+    // skip:
+    //   c = getchar()
+    //   br (c is space) skip, sign
+    // sign:
+    //   br (c == '-') minus, digits
+    // minus:
+    //   c = getchar()
+    // digits:
+    //   br (c is digit) accum, done
+    // accum:
+    //   acc = acc * 10 + (c - '0')
+    //   c = getchar()
+    //   br digits
+    // done:
+    //   ret negative ? -acc : acc
+    let function = begin(env, "read_i32");
+    let skip = env.ctx.append_basic_block(function, "skip");
+    let sign = env.ctx.append_basic_block(function, "sign");
+    let minus = env.ctx.append_basic_block(function, "minus");
+    let digits = env.ctx.append_basic_block(function, "digits");
+    let accum = env.ctx.append_basic_block(function, "accum");
+    let done = env.ctx.append_basic_block(function, "done");
+
+    let i32_type = env.ctx.i32_type();
+    let char_const = |c: char| i32_type.const_int(c as u64, false);
+    let c_ptr = env.builder.build_alloca(i32_type, "c");
+    let acc_ptr = env.builder.build_alloca(i32_type, "acc");
+    env.builder.build_store(acc_ptr, i32_type.const_zero());
+    env.builder.build_unconditional_branch(skip);
+
+    env.builder.position_at_end(skip);
+    let c = getchar(env);
+    env.builder.build_store(c_ptr, c);
+    let mut is_space = env.ctx.bool_type().const_zero();
+    for space in [' ', '\n', '\t', '\r'] {
+        let eq = env
+            .builder
+            .build_int_compare(IntPredicate::EQ, c, char_const(space), "");
+        is_space = env.builder.build_or(is_space, eq, "");
+    }
+    env.builder.build_conditional_branch(is_space, skip, sign);
+
+    env.builder.position_at_end(sign);
+    let negative = env
+        .builder
+        .build_int_compare(IntPredicate::EQ, c, char_const('-'), "");
+    env.builder
+        .build_conditional_branch(negative, minus, digits);
+
+    env.builder.position_at_end(minus);
+    let c = getchar(env);
+    env.builder.build_store(c_ptr, c);
+    env.builder.build_unconditional_branch(digits);
+
+    env.builder.position_at_end(digits);
+    let c = env.builder.build_load(c_ptr, "").into_int_value();
+    let ge = env
+        .builder
+        .build_int_compare(IntPredicate::SGE, c, char_const('0'), "");
+    let le = env
+        .builder
+        .build_int_compare(IntPredicate::SLE, c, char_const('9'), "");
+    let is_digit = env.builder.build_and(ge, le, "");
+    env.builder.build_conditional_branch(is_digit, accum, done);
+
+    env.builder.position_at_end(accum);
+    let acc = env.builder.build_load(acc_ptr, "").into_int_value();
+    let acc = env
+        .builder
+        .build_int_mul(acc, i32_type.const_int(10, false), "");
+    let d = env.builder.build_int_sub(c, char_const('0'), "");
+    let acc = env.builder.build_int_add(acc, d, "");
+    env.builder.build_store(acc_ptr, acc);
+    let c = getchar(env);
+    env.builder.build_store(c_ptr, c);
+    env.builder.build_unconditional_branch(digits);
+
+    env.builder.position_at_end(done);
+    let acc = env.builder.build_load(acc_ptr, "").into_int_value();
+    let neg = env.builder.build_int_neg(acc, "");
+    let result = env.builder.build_select(negative, neg, acc, "");
+    env.builder.build_return(Some(&result));
+}
@@ -1,10 +1,13 @@
+mod render;
+
 use std::fs;
 
-use anyhow::Error;
 use clap::Parser;
-use ipulang_codegen::codegen::code_gen;
-use ipulang_parser::{ast::program_parser, nodes::Span};
-use ipulang_typecheck::type_check::type_check;
+use ipulang_codegen::codegen::{code_gen_with_options, Options};
+use ipulang_parser::{ast::program_parser, diagnostics::Diagnostic, nodes::Span};
+use ipulang_typecheck::type_check::type_check_with_warnings;
+
+use crate::render::render;
 
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
@@ -18,22 +21,45 @@ struct Args {
     /// Number of times to greet
     #[clap(short, long, default_value_t = 1)]
     count: u8,
+    /// 配列の添字の範囲を実行時に調べない
+    #[clap(long)]
+    no_bounds_check: bool,
 }
 
-pub fn compile(code: String) -> Result<String, Box<Error>> {
-    let code = Span::new(code.as_str());
-    let ast = program_parser(code);
-    let ast = type_check(ast)?;
-    dbg!(&ast);
-    let ir = code_gen(ast)?;
-    Ok(ir)
+/// IR と警告を返す. 失敗した時はエラーを全て返す
+pub fn compile(
+    code: &str,
+    options: &Options,
+) -> Result<(String, Vec<Diagnostic>), Vec<Diagnostic>> {
+    let code = Span::new(code);
+    let ast = program_parser(code)
+        .map_err(|errors| errors.into_iter().map(Diagnostic::from).collect::<Vec<_>>())?;
+    let (ast, warnings) = type_check_with_warnings(ast)?;
+    let ir = code_gen_with_options(ast, options).map_err(|e| vec![e])?;
+    Ok((ir, warnings))
 }
 
 fn main() {
     let args = Args::parse();
 
     let code = fs::read_to_string(&args.file).unwrap();
-    let ir = compile(code).unwrap();
+    let options = Options {
+        bounds_check: !args.no_bounds_check,
+    };
+    let ir = match compile(&code, &options) {
+        Ok((ir, warnings)) => {
+            for warning in warnings.iter() {
+                eprintln!("{}", render(warning, &args.file, &code));
+            }
+            ir
+        }
+        Err(errors) => {
+            for error in errors.iter() {
+                eprintln!("{}", render(error, &args.file, &code));
+            }
+            std::process::exit(1);
+        }
+    };
 
     // let bin = ast.gen_code();
     fs::write(&args.output, ir).unwrap();
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+use ipulang_parser::diagnostics::{Diagnostic, Label};
+
+/// rustc 風にソースの該当行と下線を付けて表示する
+///
+/// ```text
+/// error[E0101]: mismatched types
+///  --> main.ipu:3:14
+///   |
+/// 3 |     return a + 1;
+///   |              ^ `bool` and `int32` cannot be mixed
+///   |            - this is `bool`
+///   |
+///   = help: both operands must have the same type
+/// ```
+pub fn render(diag: &Diagnostic, file: &str, source: &str) -> String {
+    let mut out = format!("{}[{}]: {}\n", diag.severity, diag.code, diag.message);
+
+    // 行ごとにまとめる. primary を先に表示する
+    let mut lines: BTreeMap<u32, Vec<&Label>> = BTreeMap::new();
+    for label in diag.labels.iter() {
+        lines.entry(label.location.line).or_default().push(label);
+    }
+    for labels in lines.values_mut() {
+        labels.sort_by_key(|label| !label.primary);
+    }
+
+    let width = lines.keys().last().map_or(1, |line| line.to_string().len());
+    let pad = " ".repeat(width);
+
+    if let Some(label) = diag.primary().or_else(|| diag.labels.first()) {
+        let loc = label.location;
+        out += &format!("{}--> {}:{}:{}\n", pad, file, loc.line, loc.column);
+    }
+
+    if !lines.is_empty() {
+        out += &format!("{} |\n", pad);
+        let mut prev = None;
+        for (line, labels) in lines.iter() {
+            if let Some(prev) = prev {
+                if line - prev > 1 {
+                    out += "...\n";
+                }
+            }
+            prev = Some(*line);
+
+            let text = source.lines().nth(*line as usize - 1).unwrap_or("");
+            out += &format!("{:>width$} | {}\n", line, text, width = width);
+            for label in labels {
+                out += &format!("{} | {}\n", pad, underline(label, text, source));
+            }
+        }
+    }
+
+    if !diag.notes.is_empty() || diag.help.is_some() {
+        out += &format!("{} |\n", pad);
+    }
+    for note in diag.notes.iter() {
+        out += &format!("{} = note: {}\n", pad, note);
+    }
+    if let Some(help) = &diag.help {
+        out += &format!("{} = help: {}\n", pad, help);
+    }
+    out
+}
+
+/// `    ^^^ message`
+fn underline(label: &Label, text: &str, source: &str) -> String {
+    let loc = label.location;
+    // 列の位置まではタブをそのまま使って揃える
+    let indent: String = text
+        .chars()
+        .take(loc.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    // 複数行にまたがる時は行末まで
+    let spanned = source
+        .get(loc.offset..loc.offset + loc.len)
+        .unwrap_or("")
+        .split('\n')
+        .next()
+        .unwrap_or("");
+    let marker = if label.primary { "^" } else { "-" };
+    let mut line = indent + &marker.repeat(spanned.chars().count().max(1));
+    if !label.message.is_empty() {
+        line.push(' ');
+        line.push_str(&label.message);
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipulang_parser::diagnostics::Location;
+
+    #[test]
+    fn test_render() {
+        let source = "fn main(): i32 {\n    var a: bool = 1 < 2;\n    return a + 1;\n}\n";
+        let diag = Diagnostic::error("E0101", "mismatched types")
+            .with_label(Location::new(3, 14, 55, 1), "cannot be mixed", true)
+            .with_label(Location::new(3, 12, 53, 1), "this is `bool`", false)
+            .with_label(Location::new(1, 1, 0, 2), "in this function", false)
+            .with_note("a note")
+            .with_help("a help");
+        let expected = r#"error[E0101]: mismatched types
+ --> main.ipu:3:14
+  |
+1 | fn main(): i32 {
+  | -- in this function
+...
+3 |     return a + 1;
+  |              ^ cannot be mixed
+  |            - this is `bool`
+  |
+  = note: a note
+  = help: a help
+"#;
+        assert_eq!(render(&diag, "main.ipu", source), expected);
+    }
+
+    #[test]
+    fn test_render_without_label() {
+        let diag = Diagnostic::warning("W0001", "unreachable statement").with_note("in `main`");
+        let expected = "warning[W0001]: unreachable statement\n  |\n  = note: in `main`\n";
+        assert_eq!(render(&diag, "main.ipu", ""), expected);
+    }
+}
@@ -2,389 +2,750 @@ use std::str::FromStr;
 
 use nom::{
     branch::alt,
-    bytes::complete::tag,
-    character::complete::{alpha1, alphanumeric0, char, digit1, multispace0, multispace1, one_of},
-    combinator::{map, opt},
-    multi::{many0, many1, separated_list0},
-    sequence::{delimited, preceded, terminated, tuple},
-    IResult,
+    bytes::complete::{is_a, tag, take_while},
+    character::complete::{alpha1, char, digit1, hex_digit1, none_of, one_of, satisfy},
+    combinator::{consumed, cut, eof, map, map_opt, not, opt, recognize, value, verify},
+    error::context,
+    multi::{many0, separated_list0},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    Slice,
 };
 
 use nom_locate::position;
 
+use crate::error::{IResult, ParseError};
+
 use crate::nodes::{
-    Assign, BinOp, Call, Const, Expr, For, FunctionDecl, IfElse, Op, Program, Span, Stmt, Stmts,
-    Variable, VariableDecl,
+    ArmBody, ArrayLiteral, Assign, BinOp, Call, Cast, Const, EnumDecl, Expr, ExternFn, Field,
+    FieldInit, For, FunctionDecl, GlobalDecl, IfElse, Index, IntLiteral, Item, Match, MatchArm, Op,
+    Pattern, Program, Span, Stmt, Stmts, StructDecl, StructLiteral, UnOp, UnaryOp, Variable,
+    VariableDecl, Variant, VariantLiteral, VariantPattern, While,
 };
 
+use crate::trivia::{comment_parser, skip_quoted, trivia0, trivia1};
 use crate::types::Type;
 
+/// キーワード. 失敗した時はキーワードそのものを期待していたことにする
+/// `elsewhere` のような識別子の先頭には当てはまらない
+pub fn keyword<'a>(kw: &'static str) -> impl FnMut(Span<'a>) -> IResult<Span<'a>, Span<'a>> {
+    move |s: Span<'a>| {
+        terminated(
+            tag(kw),
+            not(satisfy(|c| c.is_ascii_alphanumeric() || c == '_')),
+        )(s)
+        .map_err(|e: nom::Err<ParseError>| e.map(|_| ParseError::new(s, format!("`{}`", kw))))
+    }
+}
+
 pub fn type_parser(s: Span) -> IResult<Span, Type> {
     alt((
-        map(tag("i32"), |_| Type::Int32),
-        map(tag("i64"), |_| Type::Int64),
-        map(tag("bool"), |_| Type::Bool),
-        map(tag("string"), |_| Type::String),
-        map(tag("unit"), |_| Type::Unit),
+        map(var_name_parser, |(_, name)| match name.as_str() {
+            "i32" => Type::Int32,
+            "i64" => Type::Int64,
+            "u32" => Type::UInt32,
+            "u64" => Type::UInt64,
+            "bool" => Type::Bool,
+            "string" => Type::String,
+            "unit" => Type::Unit,
+            // それ以外の名前は構造体. あるかどうかは型検査で調べる
+            _ => Type::Named(name),
+        }),
+        array_type_parser,
+        map(
+            preceded(char('*'), cut(context("type", type_parser))),
+            |inner| Type::Pointer(Box::new(inner)),
+        ),
+    ))(s)
+}
+
+/// 配列型. `[i32; 16]`. 長さには定数の名前も書ける `[i32; N]`
+/// LLVM の配列型の長さは 32 bit なので, それを超える長さは書けない
+fn array_type_parser(s: Span) -> IResult<Span, Type> {
+    let (s, elem) = preceded(
+        char('['),
+        cut(delimited(trivia0, context("type", type_parser), trivia0)),
+    )(s)?;
+    let (s, ty) = cut(delimited(
+        pair(char(';'), trivia0),
+        context(
+            "array length",
+            alt((
+                map_opt(integer_parser, |len| {
+                    let len = u32::try_from(integer_value(len.fragment())).ok()?;
+                    Some(Type::Array(Box::new(elem.clone()), len as u64))
+                }),
+                map(var_name_parser, |(_, name)| {
+                    Type::ArrayConstLen(Box::new(elem.clone()), name)
+                }),
+            )),
+        ),
+        pair(trivia0, char(']')),
+    ))(s)?;
+    Ok((s, ty))
+}
+
+/// 数字の並び. `_` で区切ってもよい
+fn digits<'a>(
+    digit: fn(Span<'a>) -> IResult<Span<'a>, Span<'a>>,
+) -> impl FnMut(Span<'a>) -> IResult<Span<'a>, Span<'a>> {
+    recognize(pair(digit, many0(preceded(char('_'), digit))))
+}
+
+/// 整数リテラル. 符号を一緒に読むことで i32::MIN なども書ける
+/// `0xff`, `0b1010`, `1_000_000` も書ける
+fn integer_parser(s: Span) -> IResult<Span, Span> {
+    recognize(pair(
+        opt(char('-')),
+        alt((
+            preceded(
+                tag("0x"),
+                cut(context("hexadecimal digits", digits(hex_digit1))),
+            ),
+            preceded(
+                tag("0b"),
+                cut(context("binary digits", digits(|s| is_a("01")(s)))),
+            ),
+            digits(digit1),
+        )),
     ))(s)
 }
 
+/// 整数リテラルの値. どの型にも入らないほど大きい値は i128::MAX にしておき, 範囲外として扱わせる
+fn integer_value(s: &str) -> i128 {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let (radix, s) = if let Some(s) = s.strip_prefix("0x") {
+        (16, s)
+    } else if let Some(s) = s.strip_prefix("0b") {
+        (2, s)
+    } else {
+        (10, s)
+    };
+    let digits: String = s.chars().filter(|c| *c != '_').collect();
+    let value = u128::from_str_radix(&digits, radix)
+        .ok()
+        .and_then(|value| i128::try_from(value).ok())
+        .unwrap_or(i128::MAX);
+    if negative {
+        -value
+    } else {
+        value
+    }
+}
+
 /// "64hoge" -> hoge, <64>
 pub fn const_parser(s: Span) -> IResult<Span, Const> {
-    alt((
-        map(
-            tuple((digit1, char('_'), type_parser)),
-            |(n, _, t)| match t {
-                Type::Int32 => Const::I32Const(n.parse::<i32>().unwrap()),
-                Type::Int64 => Const::I64Const(n.parse::<i64>().unwrap()),
-                Type::Bool => Const::BoolConst(n.parse::<i32>().unwrap() != 0),
-                _ => panic!("unsupported type"),
-            },
+    // 範囲外の値 (-1_u32 など) はここでは読まずに int_literal_parser に任せる
+    map_opt(
+        tuple((integer_parser, char('_'), type_parser)),
+        |(n, _, t)| {
+            let value = integer_value(n.fragment());
+            match t {
+                Type::Int32 => i32::try_from(value).ok().map(Const::I32Const),
+                Type::Int64 => i64::try_from(value).ok().map(Const::I64Const),
+                Type::UInt32 => u32::try_from(value).ok().map(Const::U32Const),
+                Type::UInt64 => u64::try_from(value).ok().map(Const::U64Const),
+                Type::Bool => Some(Const::BoolConst(value != 0)),
+                _ => None,
+            }
+        },
+    )(s)
+}
+
+/// 型が決まっていない整数リテラル. 型は型検査で文脈から決める
+/// 型を書いたのに範囲外の値 (-1_u32 など) もここで読み, 型検査で報告する
+pub fn int_literal_parser(s: Span) -> IResult<Span, IntLiteral> {
+    let (s, (pos, (n, suffix))) = consumed(pair(
+        integer_parser,
+        opt(preceded(char('_'), verify(type_parser, Type::is_integer))),
+    ))(s)?;
+    let mut lit = IntLiteral::new(pos, integer_value(n.fragment()));
+    if let Some(ty) = suffix {
+        lit.ty = ty;
+    }
+    Ok((s, lit))
+}
+
+/// エスケープシーケンス. `\n`, `\t`, `\\`, `\"`, `\'`, `\0`, `\xNN` (ASCII のみ)
+/// 文字列は UTF-8 の `String` として持つので, `\x80` 以上のバイトは書けない
+fn escape_parser(s: Span) -> IResult<Span, char> {
+    preceded(
+        char('\\'),
+        cut(context(
+            "escape sequence",
+            alt((
+                value('\n', char('n')),
+                value('\t', char('t')),
+                value('\\', char('\\')),
+                value('"', char('"')),
+                value('\'', char('\'')),
+                value('\0', char('0')),
+                map(
+                    preceded(
+                        char('x'),
+                        cut(context(
+                            "hex digits from `00` to `7f` (`\\x` escapes are ASCII only)",
+                            recognize(pair(one_of("01234567"), satisfy(|c| c.is_ascii_hexdigit()))),
+                        )),
+                    ),
+                    |n: Span| char::from(u8::from_str_radix(n.fragment(), 16).unwrap()),
+                ),
+            )),
+        )),
+    )(s)
+}
+
+/// 文字リテラル `'a'`. 値はその文字のコードの整数リテラルになる
+pub fn char_literal_parser(s: Span) -> IResult<Span, IntLiteral> {
+    let (s, (pos, c)) = consumed(preceded(
+        char('\''),
+        cut(terminated(
+            alt((escape_parser, none_of("'\\\n"))),
+            char('\''),
+        )),
+    ))(s)?;
+    Ok((s, IntLiteral::new(pos, c as i128)))
+}
+
+/// 文字列リテラル `"..."`
+pub fn string_literal_parser(s: Span) -> IResult<Span, Const> {
+    map(
+        preceded(
+            char('"'),
+            cut(terminated(
+                many0(alt((escape_parser, none_of("\"\\\n")))),
+                char('"'),
+            )),
         ),
-        // リテラルを書かない時はi32
-        map(digit1, |s: Span| Const::I32Const(s.parse::<i32>().unwrap())),
-    ))(s)
+        |chars| Const::Str(chars.into_iter().collect()),
+    )(s)
 }
 
 // 変数名
 pub fn var_name_parser<'a>(s: Span<'a>) -> IResult<Span, (Span, String)> {
-    let (s, (a, b)) = tuple((alpha1, alphanumeric0))(s)?;
-    let var_name = a.fragment().to_string() + b.fragment();
-    Ok((s, (s, var_name)))
+    let (s, name) = recognize(pair(
+        alpha1,
+        take_while(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+    ))(s)?;
+    Ok((s, (name, name.fragment().to_string())))
 }
 
 // 変数
 pub fn var_parser(s: Span) -> IResult<Span, Variable> {
-    let (s, name) = var_name_parser(s)?;
-    let (_, pos) = position(s)?;
-    Ok((s, Variable::new(pos, name.1, Type::Unknown)))
+    let (s, (pos, name)) = var_name_parser(s)?;
+    Ok((s, Variable::new(pos, name, Type::Unknown)))
 }
 
 // 変数宣言
 pub fn var_decl_parser(s: Span) -> IResult<Span, VariableDecl> {
-    let (s, (_, _, name, _, _, _, typ, _, opt_init, _)) = tuple((
-        tag("var"),
-        multispace1,
-        var_name_parser,
-        // type annotation
-        multispace0,
-        char(':'),
-        multispace0,
-        type_parser,
-        multispace0,
-        map(
-            opt(tuple((
-                delimited(multispace0, char('='), multispace0),
-                or_expr_parser,
-            ))),
-            |opt| opt.map(|a| a.1),
-        ),
-        char(';'),
-    ))(s)?;
     let (s, pos) = position(s)?;
+    let (s, (name, typ, _, opt_init, _)) = preceded(
+        tuple((keyword("var"), trivia1)),
+        cut(tuple((
+            var_name_parser,
+            // type annotation. 省略したら型検査で推論する
+            map(
+                opt(preceded(
+                    delimited(trivia0, char(':'), trivia0),
+                    context("type", type_parser),
+                )),
+                |typ| typ.unwrap_or(Type::Unknown),
+            ),
+            trivia0,
+            map(
+                opt(tuple((
+                    delimited(trivia0, char('='), trivia0),
+                    cut(expr_parser),
+                ))),
+                |opt| opt.map(|a| a.1),
+            ),
+            char(';'),
+        ))),
+    )(s)?;
     Ok((s, VariableDecl::new(pos, name.1, typ, opt_init)))
 }
 
+/// 配列リテラル. 最後の要素の後ろに `,` を書いてもよい
+pub fn array_literal_parser(s: Span) -> IResult<Span, ArrayLiteral> {
+    let (s, pos) = position(s)?;
+    let (s, elems) = preceded(
+        terminated(char('['), trivia0),
+        cut(terminated(
+            separated_list0(delimited(trivia0, char(','), trivia0), expr_parser),
+            tuple((trivia0, opt(char(',')), trivia0, char(']'))),
+        )),
+    )(s)?;
+    Ok((s, ArrayLiteral::new(pos, elems)))
+}
+
+// フィールド名: 式
+fn field_init_parser(s: Span) -> IResult<Span, FieldInit> {
+    map(
+        tuple((var_name_parser, trivia0, char(':'), trivia0, expr_parser)),
+        |((pos, id), _, _, _, value)| FieldInit::new(pos, id, value),
+    )(s)
+}
+
+/// 構造体リテラル. `Point { x: 1, y: 2 }`
+pub fn struct_literal_parser(s: Span) -> IResult<Span, StructLiteral> {
+    let (s, (pos, id)) = terminated(var_name_parser, pair(trivia0, char('{')))(s)?;
+    let (s, fields) = cut(delimited(
+        trivia0,
+        separated_list0(delimited(trivia0, char(','), trivia0), field_init_parser),
+        tuple((trivia0, opt(char(',')), trivia0, char('}'))),
+    ))(s)?;
+    Ok((s, StructLiteral::new(pos, id, fields)))
+}
+
+/// バリアントの値. `Shape::Rect(1, 2)`, `Color::Red`
+pub fn variant_literal_parser(s: Span) -> IResult<Span, VariantLiteral> {
+    let (s, ((pos, enum_id), _, (_, variant))) = tuple((
+        var_name_parser,
+        tag("::"),
+        cut(context("variant name", var_name_parser)),
+    ))(s)?;
+    let (s, args) = opt(preceded(
+        terminated(char('('), trivia0),
+        cut(terminated(
+            separated_list0(delimited(trivia0, char(','), trivia0), expr_parser),
+            preceded(trivia0, char(')')),
+        )),
+    ))(s)?;
+    Ok((
+        s,
+        VariantLiteral::new(pos, enum_id, variant, args.unwrap_or_default()),
+    ))
+}
+
+// パターンのフィールドを入れる変数. 使わなければ `_`
+fn binding_parser(s: Span) -> IResult<Span, Variable> {
+    alt((
+        map(var_name_parser, |(pos, id)| {
+            Variable::new(pos, id, Type::Unknown)
+        }),
+        map(tag("_"), |pos| {
+            Variable::new(pos, "_".to_owned(), Type::Unknown)
+        }),
+    ))(s)
+}
+
+/// match のパターン. `_`, `Color::Red`, `Shape::Rect(w, _)`
+pub fn pattern_parser(s: Span) -> IResult<Span, Pattern> {
+    let variant = map(
+        tuple((
+            var_name_parser,
+            tag("::"),
+            cut(context("variant name", var_name_parser)),
+            opt(preceded(
+                terminated(char('('), trivia0),
+                cut(terminated(
+                    separated_list0(delimited(trivia0, char(','), trivia0), binding_parser),
+                    preceded(trivia0, char(')')),
+                )),
+            )),
+        )),
+        |((pos, enum_id), _, (_, variant), bindings)| {
+            Pattern::Variant(VariantPattern::new(
+                pos,
+                enum_id,
+                variant,
+                bindings.unwrap_or_default(),
+            ))
+        },
+    );
+    context("pattern", alt((map(tag("_"), Pattern::Wildcard), variant)))(s)
+}
+
+/// match の腕. `allow_block` なら `{ ... }` も書ける
+fn match_arm_parser<'a>(
+    allow_block: bool,
+) -> impl FnMut(Span<'a>) -> IResult<Span<'a>, MatchArm<'a>> {
+    move |s| {
+        let (s, pattern) = pattern_parser(s)?;
+        let (s, _) = cut(delimited(trivia0, tag("=>"), trivia0))(s)?;
+        let (s, body) = if allow_block {
+            cut(alt((
+                map(
+                    delimited(char('{'), stmts_parser, char('}')),
+                    ArmBody::Block,
+                ),
+                map(expr_parser, ArmBody::Expr),
+            )))(s)?
+        } else {
+            map(cut(expr_parser), ArmBody::Expr)(s)?
+        };
+        Ok((s, MatchArm::new(pattern, body)))
+    }
+}
+
+/// `match (expr) { pattern => body, ... }`
+/// 文の match (`allow_block`) では腕に `{ ... }` を書ける. 式の match の腕は式だけ
+fn match_parser<'a>(allow_block: bool) -> impl FnMut(Span<'a>) -> IResult<Span<'a>, Match<'a>> {
+    move |s| {
+        let (s, pos) = keyword("match")(s)?;
+        let (s, (_, expr, _, _, _, arms, _)) = cut(tuple((
+            trivia0,
+            paren_expr_parser,
+            trivia0,
+            char('{'),
+            trivia0,
+            many0(terminated(
+                match_arm_parser(allow_block),
+                tuple((trivia0, opt(char(',')), trivia0)),
+            )),
+            char('}'),
+        )))(s)?;
+        Ok((s, Match::new(pos, expr, arms)))
+    }
+}
+
 pub fn paren_expr_parser(s: Span) -> IResult<Span, Expr> {
-    delimited(char('('), or_expr_parser, char(')'))(s)
+    preceded(char('('), cut(terminated(expr_parser, char(')'))))(s)
 }
 
 pub fn call_parser(s: Span) -> IResult<Span, Call> {
-    let (s, ((_, name), args)) = tuple((
+    let (s, ((pos, name), args)) = tuple((
         var_name_parser,
-        delimited(
-            terminated(char('('), multispace0),
-            separated_list0(
-                delimited(multispace0, char(','), multispace0),
-                or_expr_parser,
-            ),
-            preceded(multispace0, char(')')),
+        preceded(
+            terminated(char('('), trivia0),
+            cut(terminated(
+                separated_list0(delimited(trivia0, char(','), trivia0), expr_parser),
+                preceded(trivia0, char(')')),
+            )),
         ),
     ))(s)?;
-    let (s, pos) = position(s)?;
     Ok((s, Call::new(pos, name, args)))
 }
 
+/// 単項演算子
+pub fn unary_op_parser(s: Span) -> IResult<Span, UnaryOp> {
+    let (s, (pos, op)) = alt((
+        map(tag("-"), |pos| (pos, UnOp::Neg)),
+        map(tag("!"), |pos| (pos, UnOp::Not)),
+        map(tag("~"), |pos| (pos, UnOp::BitNot)),
+        map(tag("&"), |pos| (pos, UnOp::Ref)),
+        map(tag("*"), |pos| (pos, UnOp::Deref)),
+    ))(s)?;
+    let (s, expr) = cut(factor_parser)(s)?;
+    Ok((s, UnaryOp::new(pos, op, expr, Type::Unknown)))
+}
+
 pub fn factor_parser(s: Span) -> IResult<Span, Expr> {
-    delimited(
-        multispace0,
+    delimited(trivia0, postfix_expr_parser, trivia0)(s)
+}
+
+/// 添字 `a[i]` とフィールド `p.x` は単項演算子より強い. `a[i].x[j]` のように続けられる
+fn postfix_expr_parser(s: Span) -> IResult<Span, Expr> {
+    let (mut s, mut expr) = context(
+        "expression",
         alt((
+            map(string_literal_parser, |c| Expr::Const(c)),
+            map(char_literal_parser, |lit| Expr::IntLiteral(lit)),
             map(const_parser, |c| Expr::Const(c)),
+            map(int_literal_parser, |lit| Expr::IntLiteral(lit)),
+            map(unary_op_parser, |u| Expr::UnaryOp(Box::new(u))),
+            map(array_literal_parser, |array| Expr::ArrayLiteral(array)),
             paren_expr_parser,
+            map(match_parser(false), |m| Expr::Match(Box::new(m))),
+            map(variant_literal_parser, |literal| {
+                Expr::VariantLiteral(literal)
+            }),
             map(call_parser, |call| Expr::Call(call)),
+            map(struct_literal_parser, |literal| {
+                Expr::StructLiteral(literal)
+            }),
             map(var_parser, |var| Expr::Variable(var)),
         )),
-        multispace0,
-    )(s)
-}
-
-pub fn multiplicative_expr_parser(s: Span) -> IResult<Span, Expr> {
-    let (s, (factor, option)) = tuple((
-        factor_parser,
-        opt(tuple((
-            map(
-                delimited(multispace0, one_of("*/%"), multispace0),
-                |c| match c {
-                    '*' => Op::Mul,
-                    '/' => Op::Div,
-                    '%' => Op::Mod,
-                    _ => panic!("unknown operator: {:?}", c),
-                },
-            ),
-            multiplicative_expr_parser,
-        ))),
-    ))(s)?;
-    let (s, pos) = position(s)?;
-    if let Some((op, expr)) = option {
-        Ok((
-            s,
-            Expr::BinOp(Box::new(BinOp::new(pos, factor, op, expr, Type::Unknown))),
-        ))
-    } else {
-        Ok((s, factor))
+    )(s)?;
+    loop {
+        let postfix_start = alt((tag::<_, _, ParseError>("["), tag(".")));
+        let (rest, pos) = match preceded(trivia0, postfix_start)(s) {
+            Ok(res) => res,
+            Err(_) => break,
+        };
+        let (rest, postfix) = if *pos.fragment() == "[" {
+            let (rest, index) = cut(terminated(
+                delimited(trivia0, expr_parser, trivia0),
+                char(']'),
+            ))(rest)?;
+            (rest, Expr::Index(Box::new(Index::new(pos, expr, index))))
+        } else {
+            let (rest, (_, id)) =
+                cut(preceded(trivia0, context("field name", var_name_parser)))(rest)?;
+            (rest, Expr::Field(Box::new(Field::new(pos, expr, id))))
+        };
+        expr = postfix;
+        s = rest;
     }
+    Ok((s, expr))
 }
 
-pub fn additive_expr_parser(s: Span) -> IResult<Span, Expr> {
-    let (s, (multi, option)) = tuple((
-        multiplicative_expr_parser,
-        opt(tuple((
-            map(
-                delimited(multispace0, one_of("+-"), multispace0),
-                |c| match c {
-                    '+' => Op::Add,
-                    '-' => Op::Sub,
-                    _ => panic!("unknown operator: {:?}", c),
-                },
-            ),
-            additive_expr_parser,
-        ))),
-    ))(s)?;
-    let (s, pos) = position(s)?;
-    if let Some((op, expr)) = option {
-        Ok((
-            s,
-            Expr::BinOp(Box::new(BinOp::new(pos, multi, op, expr, Type::Unknown))),
-        ))
-    } else {
-        Ok((s, multi))
-    }
-}
+/// 二項演算子の表 (記号, 演算子, 優先順位).
+/// 優先順位は大きいほど強く結合する. 前方一致で探すので長い記号を先に置く
+pub const BINARY_OPERATORS: [(&str, Op, u8); 15] = [
+    ("||", Op::Or, 1),
+    ("&&", Op::And, 2),
+    ("==", Op::Eq, 3),
+    ("!=", Op::Neq, 3),
+    ("<<", Op::Shl, 5),
+    (">>", Op::Shr, 5),
+    (">=", Op::Geq, 4),
+    ("<=", Op::Leq, 4),
+    (">", Op::Gt, 4),
+    ("<", Op::Lt, 4),
+    ("+", Op::Add, 6),
+    ("-", Op::Sub, 6),
+    ("*", Op::Mul, 7),
+    ("/", Op::Div, 7),
+    ("%", Op::Mod, 7),
+];
 
-pub fn relational_op_parser(s: Span) -> IResult<Span, Op> {
-    let (s, c) = delimited(
-        multispace0,
-        alt((tag(">="), tag("<="), tag(">"), tag("<"))),
-        multispace0,
-    )(s)?;
-    let (s, pos) = position(s)?;
-    let op = match *c.fragment() {
-        ">=" => Op::Geq,
-        "<=" => Op::Leq,
-        ">" => Op::Gt,
-        "<" => Op::Lt,
-        _ => panic!("unknown operator: {:?}", c),
-    };
-    Ok((s, op))
-}
-
-pub fn releational_expr_parser(s: Span) -> IResult<Span, Expr> {
-    let (s, (add, option)) = tuple((
-        additive_expr_parser,
-        opt(tuple((relational_op_parser, releational_expr_parser))),
-    ))(s)?;
-    let (s, pos) = position(s)?;
-    if let Some((op, expr)) = option {
-        Ok((
-            s,
-            Expr::BinOp(Box::new(BinOp::new(pos, add, op, expr, Type::Unknown))),
-        ))
-    } else {
-        Ok((s, add))
+/// 二項演算子. 位置と優先順位も返す
+pub fn binary_op_parser(s: Span) -> IResult<Span, (Span, Op, u8)> {
+    let (s, _) = trivia0(s)?;
+    for (symbol, op, prec) in BINARY_OPERATORS.iter() {
+        if let Ok((rest, pos)) = tag::<_, _, ParseError>(*symbol)(s) {
+            let (rest, _) = trivia0(rest)?;
+            return Ok((rest, (pos, *op, *prec)));
+        }
     }
+    Err(nom::Err::Error(ParseError::new(s, "operator")))
 }
 
-pub fn equality_op_parser(s: Span) -> IResult<Span, Op> {
-    let (s, c) = delimited(multispace0, alt((tag("=="), tag("!="))), multispace0)(s)?;
-    let (s, pos) = position(s)?;
-    let op = match *c.fragment() {
-        "==" => Op::Eq,
-        "!=" => Op::Neq,
-        _ => panic!("unknown operator: {:?}", c),
-    };
-    Ok((s, op))
-}
-
-pub fn equality_expr_parser(s: Span) -> IResult<Span, Expr> {
-    let (s, (rel, option)) = tuple((
-        releational_expr_parser,
-        opt(tuple((equality_op_parser, equality_expr_parser))),
-    ))(s)?;
-    let (s, pos) = position(s)?;
-    if let Some((op, expr)) = option {
-        Ok((
-            s,
-            Expr::BinOp(Box::new(BinOp::new(pos, rel, op, expr, Type::Unknown))),
-        ))
-    } else {
-        Ok((s, rel))
+/// `expr as i64`. 単項演算子より弱く, 二項演算子より強い. `a as i64 as i32` のように続けられる
+pub fn cast_expr_parser(s: Span) -> IResult<Span, Expr> {
+    let (mut s, mut expr) = factor_parser(s)?;
+    loop {
+        let (rest, pos) = match terminated(keyword("as"), trivia1)(s) {
+            Ok(res) => res,
+            Err(nom::Err::Error(_)) => break,
+            Err(e) => return Err(e),
+        };
+        let (rest, ty) = cut(terminated(context("type", type_parser), trivia0))(rest)?;
+        expr = Expr::Cast(Box::new(Cast::new(pos, expr, ty)));
+        s = rest;
     }
+    Ok((s, expr))
 }
 
-pub fn and_expr_parser(s: Span) -> IResult<Span, Expr> {
-    let (s, (rel, option)) = tuple((
-        equality_expr_parser,
-        opt(tuple((
-            delimited(multispace0, tag("&&"), multispace0),
-            and_expr_parser,
-        ))),
-    ))(s)?;
-    let (s, pos) = position(s)?;
-    if let Some((_, expr)) = option {
-        Ok((
-            s,
-            Expr::BinOp(Box::new(BinOp::new(pos, rel, Op::And, expr, Type::Unknown))),
-        ))
-    } else {
-        Ok((s, rel))
+/// 優先順位が `min_prec` 以上の二項演算子だけを読む (precedence climbing)
+fn binary_expr_parser(s: Span, min_prec: u8) -> IResult<Span, Expr> {
+    let (mut s, mut left) = cast_expr_parser(s)?;
+    loop {
+        let (rest, (pos, op, prec)) = match binary_op_parser(s) {
+            Ok(res) => res,
+            Err(nom::Err::Error(_)) => break,
+            Err(e) => return Err(e),
+        };
+        if prec < min_prec {
+            break;
+        }
+        // 左結合にするため, 右辺は今の演算子より強いものだけを読む
+        let (rest, right) = cut(|s| binary_expr_parser(s, prec + 1))(rest)?;
+        left = Expr::BinOp(Box::new(BinOp::new(pos, left, op, right, Type::Unknown)));
+        s = rest;
     }
+    Ok((s, left))
 }
 
-pub fn or_expr_parser(s: Span) -> IResult<Span, Expr> {
-    map(
-        tuple((
-            and_expr_parser,
-            opt(tuple((
-                delimited(multispace0, tag("||"), multispace0),
-                or_expr_parser,
-            ))),
-        )),
-        |(rel, option)| {
-            if let Some((pos, expr)) = option {
-                Expr::BinOp(Box::new(BinOp::new(pos, rel, Op::Or, expr, Type::Unknown)))
-            } else {
-                rel
-            }
-        },
-    )(s)
+pub fn expr_parser(s: Span) -> IResult<Span, Expr> {
+    binary_expr_parser(s, 0)
 }
 
 pub fn assign_parser(s: Span) -> IResult<Span, Assign> {
     map(
         tuple((
-            var_name_parser,
-            delimited(multispace0, char('='), multispace0),
-            terminated(or_expr_parser, terminated(multispace0, char(';'))),
+            position,
+            verify(factor_parser, Expr::is_place),
+            terminated(char('='), trivia0),
+            terminated(expr_parser, terminated(trivia0, char(';'))),
         )),
-        |(id, _, expr)| Assign::new(id.0, id.1, expr),
+        |(pos, left, _, expr)| Assign::new(pos, left, expr),
     )(s)
 }
 
 pub fn return_parser(s: Span) -> IResult<Span, Expr> {
-    let (s, (_, _, expr, _)) = tuple((tag("return"), multispace1, or_expr_parser, char(';')))(s)?;
+    let (s, (_, _, expr, _)) =
+        tuple((keyword("return"), trivia1, cut(expr_parser), cut(char(';'))))(s)?;
     Ok((s, expr))
 }
 
 pub fn if_else_parser(s: Span) -> IResult<Span, IfElse> {
     map(
         tuple((
-            tag("if"),
-            multispace0,
-            // cond
-            delimited(
+            keyword("if"),
+            trivia0,
+            preceded(
                 char('('),
-                delimited(multispace0, or_expr_parser, multispace0),
-                char(')'),
-            ),
-            // success
-            delimited(
-                multispace0,
-                delimited(char('{'), stmts_parser, char('}')),
-                multispace0,
+                cut(tuple((
+                    // cond
+                    terminated(delimited(trivia0, expr_parser, trivia0), char(')')),
+                    // success
+                    delimited(
+                        trivia0,
+                        delimited(char('{'), stmts_parser, char('}')),
+                        trivia0,
+                    ),
+                    // failure
+                    // else if はネストした if else にする
+                    opt(preceded(
+                        keyword("else"),
+                        cut(terminated(
+                            alt((
+                                map(preceded(trivia1, if_else_parser), |if_else| {
+                                    Stmts::new(vec![Stmt::IfElse(if_else)])
+                                }),
+                                preceded(trivia0, delimited(char('{'), stmts_parser, char('}'))),
+                            )),
+                            trivia0,
+                        )),
+                    )),
+                ))),
             ),
-            opt(tag("else")),
-            // failure
-            opt(delimited(
-                multispace0,
-                delimited(char('{'), stmts_parser, char('}')),
-                multispace0,
-            )),
         )),
-        |(tag, _, cond, sucess, _, failure)| IfElse::new(tag, cond, sucess, failure),
+        |(tag, _, (cond, sucess, failure))| IfElse::new(tag, cond, sucess, failure),
     )(s)
 }
 pub fn for_parser(s: Span) -> IResult<Span, For> {
     map(
         tuple((
-            tag("for"),
-            multispace0,
-            // var_decl, cond, assign
-            delimited(
+            keyword("for"),
+            trivia0,
+            preceded(
                 char('('),
-                tuple((
-                    delimited(multispace0, var_decl_parser, multispace0),
-                    delimited(multispace0, or_expr_parser, multispace0),
-                    delimited(multispace0, char(';'), multispace0),
-                    delimited(multispace0, assign_parser, multispace0),
-                )),
-                char(')'),
+                cut(tuple((
+                    // var_decl, cond, assign
+                    terminated(
+                        tuple((
+                            delimited(trivia0, var_decl_parser, trivia0),
+                            delimited(trivia0, expr_parser, trivia0),
+                            delimited(trivia0, char(';'), trivia0),
+                            delimited(trivia0, assign_parser, trivia0),
+                        )),
+                        char(')'),
+                    ),
+                    delimited(
+                        trivia0,
+                        delimited(char('{'), stmts_parser, char('}')),
+                        trivia0,
+                    ),
+                ))),
             ),
-            delimited(
-                multispace0,
-                delimited(char('{'), stmts_parser, char('}')),
-                multispace0,
+        )),
+        |(tag, _, ((var_decl, cond, _, assign), stmts))| {
+            For::new(tag, var_decl, cond, assign, stmts)
+        },
+    )(s)
+}
+
+pub fn while_parser(s: Span) -> IResult<Span, While> {
+    map(
+        tuple((
+            keyword("while"),
+            trivia0,
+            preceded(
+                char('('),
+                cut(tuple((
+                    // cond
+                    terminated(delimited(trivia0, expr_parser, trivia0), char(')')),
+                    delimited(
+                        trivia0,
+                        delimited(char('{'), stmts_parser, char('}')),
+                        trivia0,
+                    ),
+                ))),
             ),
         )),
-        |(tag, _, (var_decl, cond, _, assign), stmts)| For::new(tag, var_decl, cond, assign, stmts),
+        |(tag, _, (cond, stmts))| While::new(tag, cond, stmts),
     )(s)
 }
 
 pub fn stmt_parser(s: Span) -> IResult<Span, Stmt> {
     delimited(
-        multispace0,
-        alt((
-            map(var_decl_parser, |v| Stmt::VariableDecl(v)),
-            map(return_parser, |r| Stmt::Return(r)),
-            map(assign_parser, |a| Stmt::Assign(a)),
-            map(if_else_parser, |i| Stmt::IfElse(i)),
-            map(for_parser, |i| Stmt::For(i)),
-            map(
-                tuple((or_expr_parser, multispace0, char(';'))),
-                |(expr, _, _)| Stmt::Expr(expr),
-            ),
-        )),
-        multispace0,
+        trivia0,
+        context(
+            "statement",
+            alt((
+                map(var_decl_parser, |v| Stmt::VariableDecl(v)),
+                map(return_parser, |r| Stmt::Return(r)),
+                map(if_else_parser, |i| Stmt::IfElse(i)),
+                map(for_parser, |i| Stmt::For(i)),
+                map(while_parser, |w| Stmt::While(w)),
+                map(
+                    tuple((keyword("break"), trivia0, char(';'))),
+                    |(tag, _, _)| Stmt::Break(tag),
+                ),
+                map(
+                    tuple((keyword("continue"), trivia0, char(';'))),
+                    |(tag, _, _)| Stmt::Continue(tag),
+                ),
+                map(match_parser(true), |m| Stmt::Match(m)),
+                // 左辺は式として読むので, `for (` などを呼び出しと間違えないように後ろに置く
+                map(assign_parser, |a| Stmt::Assign(a)),
+                map(tuple((expr_parser, trivia0, char(';'))), |(expr, _, _)| {
+                    Stmt::Expr(expr)
+                }),
+            )),
+        ),
+        trivia0,
     )(s)
 }
 
+/// ブロックの終わり
+fn stmts_end_parser(s: Span) -> IResult<Span, Span> {
+    preceded(trivia0, alt((tag("}"), eof)))(s)
+}
+
 pub fn stmts_parser(s: Span) -> IResult<Span, Stmts> {
-    let (s, stmts) = delimited(multispace0, many0(stmt_parser), multispace0)(s)?;
+    // `}` が来るまでは文が続くので, 途中で失敗したらそこでエラーにする
+    let (s, stmts) = delimited(
+        trivia0,
+        many0(preceded(not(stmts_end_parser), cut(stmt_parser))),
+        trivia0,
+    )(s)?;
     Ok((s, Stmts::new(stmts)))
 }
 
 // pub fn trim_parser(s: Span, keyword: Span) -> IResult<Span, String> {
-//     delimited(multispace0, tag(keyword), multispace0)(s)
+//     delimited(trivia0, tag(keyword), trivia0)(s)
 // }
 
+// 引数名: 型
+fn parameter_parser(s: Span) -> IResult<Span, Variable> {
+    map(
+        tuple((var_name_parser, trivia0, char(':'), trivia0, type_parser)),
+        |(id, _, _, _, typ)| Variable::new(id.0, id.1, typ),
+    )(s)
+}
+
 pub fn function_parameters_parser(s: Span) -> IResult<Span, Vec<Variable>> {
     delimited(
         char('('),
         delimited(
-            multispace0,
-            separated_list0(
-                tuple((multispace0, char(','), multispace0)),
+            trivia0,
+            separated_list0(tuple((trivia0, char(','), trivia0)), parameter_parser),
+            trivia0,
+        ),
+        char(')'),
+    )(s)
+}
+
+/// extern fn の引数. 最後に `...` を書くと可変長引数になる
+fn extern_parameters_parser(s: Span) -> IResult<Span, (Vec<Variable>, bool)> {
+    delimited(
+        char('('),
+        delimited(
+            trivia0,
+            pair(
+                separated_list0(tuple((trivia0, char(','), trivia0)), parameter_parser),
                 map(
-                    tuple((
-                        var_name_parser,
-                        multispace0,
-                        char(':'),
-                        multispace0,
-                        type_parser,
-                    )),
-                    |(id, _, _, _, typ)| Variable::new(id.0, id.1, typ),
+                    opt(preceded(tuple((trivia0, char(','), trivia0)), tag("..."))),
+                    |dots| dots.is_some(),
                 ),
             ),
-            multispace0,
+            trivia0,
         ),
         char(')'),
     )(s)
@@ -394,42 +755,270 @@ pub fn function_parameters_parser(s: Span) -> IResult<Span, Vec<Variable>> {
 pub fn function_decl_parser(s: Span) -> IResult<Span, FunctionDecl> {
     map(
         tuple((
-            tag("fn"),
-            multispace1,
-            var_name_parser,
-            multispace0,
-            function_parameters_parser,
-            multispace0,
-            char(':'),
-            multispace0,
-            type_parser,
-            multispace0,
-            delimited(
-                multispace0,
-                delimited(char('{'), stmts_parser, char('}')),
-                multispace0,
-            ),
+            keyword("fn"),
+            trivia1,
+            cut(tuple((
+                var_name_parser,
+                trivia0,
+                function_parameters_parser,
+                trivia0,
+                char(':'),
+                trivia0,
+                context("type", type_parser),
+                trivia0,
+                delimited(
+                    trivia0,
+                    delimited(char('{'), stmts_parser, char('}')),
+                    trivia0,
+                ),
+            ))),
         )),
-        |(tag, _, name, _, params, _, _, _, typ, _, stmts)| {
+        |(tag, _, (name, _, params, _, _, _, typ, _, stmts))| {
             FunctionDecl::new(tag, name.1, params, typ, stmts)
         },
     )(s)
 }
 
-pub fn program_parser(s: Span) -> Program {
-    let (ss, ast) = many1(delimited(multispace0, function_decl_parser, multispace0))(s).unwrap();
-    assert_eq!(
-        ss.fragment().to_string(),
-        "",
-        "program parser must consume all string"
-    );
-    Program::new(ast)
+// 本体のない関数宣言
+pub fn extern_fn_parser(s: Span) -> IResult<Span, ExternFn> {
+    map(
+        tuple((
+            keyword("extern"),
+            trivia1,
+            cut(tuple((
+                keyword("fn"),
+                trivia1,
+                var_name_parser,
+                trivia0,
+                extern_parameters_parser,
+                trivia0,
+                char(':'),
+                trivia0,
+                context("type", type_parser),
+                trivia0,
+                char(';'),
+            ))),
+        )),
+        |(tag, _, (_, _, name, _, (params, variadic), _, _, _, typ, _, _))| {
+            ExternFn::new(tag, name.1, params, typ, variadic)
+        },
+    )(s)
+}
+
+/// 構造体の宣言. `struct Point { x: i32, y: i32 }`
+pub fn struct_decl_parser(s: Span) -> IResult<Span, StructDecl> {
+    map(
+        tuple((
+            keyword("struct"),
+            trivia1,
+            cut(tuple((
+                var_name_parser,
+                trivia0,
+                char('{'),
+                trivia0,
+                separated_list0(delimited(trivia0, char(','), trivia0), parameter_parser),
+                tuple((trivia0, opt(char(',')), trivia0, char('}'))),
+            ))),
+        )),
+        |(tag, _, (name, _, _, _, fields, _))| StructDecl::new(tag, name.1, fields),
+    )(s)
+}
+
+// バリアント. `Rect(i32, i32)`, `Red`
+fn variant_parser(s: Span) -> IResult<Span, Variant> {
+    map(
+        pair(
+            var_name_parser,
+            opt(preceded(
+                terminated(char('('), trivia0),
+                cut(terminated(
+                    separated_list0(
+                        delimited(trivia0, char(','), trivia0),
+                        context("type", type_parser),
+                    ),
+                    preceded(trivia0, char(')')),
+                )),
+            )),
+        ),
+        |((pos, id), fields)| Variant::new(pos, id, fields.unwrap_or_default()),
+    )(s)
+}
+
+/// enum の宣言. `enum Shape { Circle(i32), Rect(i32, i32), Empty }`
+pub fn enum_decl_parser(s: Span) -> IResult<Span, EnumDecl> {
+    map(
+        tuple((
+            keyword("enum"),
+            trivia1,
+            cut(tuple((
+                var_name_parser,
+                trivia0,
+                char('{'),
+                trivia0,
+                separated_list0(delimited(trivia0, char(','), trivia0), variant_parser),
+                tuple((trivia0, opt(char(',')), trivia0, char('}'))),
+            ))),
+        )),
+        |(tag, _, (name, _, _, _, variants, _))| EnumDecl::new(tag, name.1, variants),
+    )(s)
+}
+
+/// 定数 `const N: i32 = 30;` か, グローバル変数 `static mut n: i32 = 0;`
+/// 型と初期値は省略できない
+pub fn global_decl_parser(s: Span) -> IResult<Span, GlobalDecl> {
+    let (s, (pos, mutable)) = alt((
+        map(keyword("const"), |pos| (pos, false)),
+        map(
+            tuple((keyword("static"), trivia1, cut(keyword("mut")))),
+            |(pos, _, _)| (pos, true),
+        ),
+    ))(s)?;
+    let (s, (_, ((_, id), _, _, _, ty, _, _, _, init, _, _))) = tuple((
+        trivia1,
+        cut(tuple((
+            var_name_parser,
+            trivia0,
+            char(':'),
+            trivia0,
+            context("type", type_parser),
+            trivia0,
+            char('='),
+            trivia0,
+            expr_parser,
+            trivia0,
+            char(';'),
+        ))),
+    ))(s)?;
+    Ok((s, GlobalDecl::new(pos, id, ty, init, mutable)))
+}
+
+/// 関数宣言などトップレベルの要素の始まりかどうか
+fn is_item_start(s: Span) -> bool {
+    alt((
+        keyword("fn"),
+        keyword("extern"),
+        keyword("struct"),
+        keyword("enum"),
+        keyword("const"),
+        keyword("static"),
+    ))(s)
+    .and_then(|(s, _)| trivia1(s))
+    .is_ok()
+}
+
+// トップレベルの要素
+pub fn item_parser(s: Span) -> IResult<Span, Item> {
+    alt((
+        map(function_decl_parser, Item::Function),
+        map(extern_fn_parser, Item::ExternFn),
+        map(struct_decl_parser, Item::Struct),
+        map(enum_decl_parser, Item::Enum),
+        map(global_decl_parser, Item::Global),
+    ))(s)
+}
+
+/// エラーの後, 次の `;` か `}` の後ろ, もしくは次の関数宣言の前まで読み飛ばす
+fn skip_to_sync_point(s: Span) -> Span {
+    let mut rest = s;
+    let mut prev = ' ';
+    while let Some(c) = rest.fragment().chars().next() {
+        // コメントや文字列の中の `;` などは区切りにしない
+        if let Ok((after, _)) = comment_parser(rest) {
+            rest = after;
+            prev = ' ';
+            continue;
+        }
+        if let Some(after) = skip_quoted(rest) {
+            rest = after;
+            prev = ' ';
+            continue;
+        }
+        if c == ';' || c == '}' {
+            return rest.slice(c.len_utf8()..);
+        }
+        if rest.location_offset() > s.location_offset()
+            && prev.is_whitespace()
+            && is_item_start(rest)
+        {
+            return rest;
+        }
+        prev = c;
+        rest = rest.slice(c.len_utf8()..);
+    }
+    rest
+}
+
+fn into_parse_error(e: nom::Err<ParseError>) -> ParseError {
+    match e {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e,
+        nom::Err::Incomplete(_) => unreachable!("complete parsers never return Incomplete"),
+    }
+}
+
+/// 構文エラーがあっても文, 関数の区切りまで読み飛ばして続きを読み, エラーをまとめて返す
+pub fn program_parser(s: Span) -> Result<Program, Vec<ParseError>> {
+    let mut items = vec![];
+    let mut errors = vec![];
+    // 関数の途中で失敗した後は, 次の関数宣言までを文として読む
+    let mut recovering = false;
+    let mut s = s;
+    loop {
+        s = match trivia0(s) {
+            Ok((rest, _)) => rest,
+            // 閉じていないコメントで最後まで読んでしまった
+            Err(e) => {
+                errors.push(into_parse_error(e));
+                break;
+            }
+        };
+        if s.fragment().is_empty() {
+            break;
+        }
+        if recovering && !is_item_start(s) {
+            if s.fragment().starts_with('}') {
+                s = s.slice(1..);
+                continue;
+            }
+            match stmt_parser(s) {
+                Ok((rest, _)) => s = rest,
+                Err(e) => {
+                    let e = into_parse_error(e);
+                    s = skip_to_sync_point(s.slice(e.offset - s.location_offset()..));
+                    errors.push(e);
+                }
+            }
+            continue;
+        }
+        recovering = false;
+        match item_parser(s) {
+            Ok((rest, item)) => {
+                items.push(item);
+                s = rest;
+            }
+            Err(e) => {
+                let e = into_parse_error(e);
+                s = skip_to_sync_point(s.slice(e.offset - s.location_offset()..));
+                errors.push(e);
+                recovering = true;
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(Program::new(items))
+    } else {
+        Err(errors)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// 型を書かない整数リテラル. 位置は比較しない
+    fn int(n: i128) -> Expr<'static> {
+        Expr::IntLiteral(IntLiteral::new(Span::new(""), n))
+    }
+
     fn check_consumed(code: Span, res: Span) {
         assert_eq!(
             res.fragment().to_string(),
@@ -441,7 +1030,7 @@ mod tests {
 
     #[test]
     fn test_const() {
-        let codes: Vec<Span> = vec!["4", "20", "0_i32", "10_i64", "0_bool", "1_bool"]
+        let codes: Vec<Span> = vec!["0_i32", "10_i64", "0_bool", "1_bool", "0xff_u32"]
             .iter()
             .map(|code| Span::new(code))
             .collect();
@@ -452,6 +1041,179 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_int_literal() {
+        let codes = vec![
+            ("4", 4),
+            ("-1", -1),
+            ("-2147483648", i32::MIN as i128),
+            ("3000000000", 3000000000),
+            ("1_000_000", 1000000),
+            ("0xff", 255),
+            ("0xFF_FF", 65535),
+            ("-0x80000000", i32::MIN as i128),
+            ("0b1010", 10),
+            ("0b1111_0000", 240),
+            // どの型にも入らない値も読める. 範囲は型検査で調べる
+            ("340282366920938463463374607431768211456", i128::MAX),
+        ];
+
+        for (code, expect) in codes {
+            let code = Span::new(code);
+            let (res, lit) = int_literal_parser(code).unwrap();
+            check_consumed(code, res);
+            assert_eq!(lit.value, expect, "{}", code);
+            assert_eq!(lit.position.fragment(), code.fragment());
+        }
+
+        let err = program_parser(Span::new("fn main(): i32 { return 0x; }")).unwrap_err();
+        assert_eq!(
+            err[0].to_string(),
+            "1:27: expected hexadecimal digits, found `;`"
+        );
+    }
+
+    #[test]
+    fn test_string_literal() {
+        let codes = vec![
+            (r#""""#, ""),
+            (r#""hello, world""#, "hello, world"),
+            (r#""a\nb\tc""#, "a\nb\tc"),
+            (r#""\\ \" \' \0""#, "\\ \" ' \0"),
+            (r#""\x41\x7f""#, "A\x7f"),
+            (r#""// not a comment;""#, "// not a comment;"),
+        ];
+        for (code, expect) in codes {
+            let code = Span::new(code);
+            let (res, c) = string_literal_parser(code).unwrap();
+            check_consumed(code, res);
+            assert_eq!(c, Const::new_str(expect));
+        }
+
+        let errors = vec![
+            (
+                r#"fn main(): unit { "\q"; }"#,
+                "1:21: expected escape sequence, found `q`",
+            ),
+            (
+                r#"fn main(): unit { "\x80"; }"#,
+                "1:22: expected hex digits from `00` to `7f` (`\\x` escapes are ASCII only), found `80`",
+            ),
+            (
+                "fn main(): unit { \"abc\n }",
+                "1:23: expected `\"`, found `\n`",
+            ),
+        ];
+        for (code, expect) in errors {
+            let err = program_parser(Span::new(code)).unwrap_err();
+            assert_eq!(err[0].to_string(), expect, "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_char_literal() {
+        let codes = vec![
+            ("'a'", 97),
+            ("'\\n'", 10),
+            ("'\\''", 39),
+            ("'\"'", 34),
+            ("'あ'", 0x3042),
+        ];
+        for (code, expect) in codes {
+            let code = Span::new(code);
+            let (res, lit) = char_literal_parser(code).unwrap();
+            check_consumed(code, res);
+            assert_eq!(lit.value, expect, "{}", code);
+            assert_eq!(lit.position.fragment(), code.fragment());
+        }
+        assert!(char_literal_parser(Span::new("''")).is_err());
+        assert!(char_literal_parser(Span::new("'ab'")).is_err());
+    }
+
+    #[test]
+    fn test_negative_const() {
+        let codes = vec![
+            ("-1_i32", Const::new_i32(-1)),
+            ("-2147483648_i32", Const::new_i32(i32::MIN)),
+            ("2147483647_i32", Const::new_i32(i32::MAX)),
+            ("-9223372036854775808_i64", Const::new_i64(i64::MIN)),
+        ];
+
+        for (code, expect) in codes {
+            let code = Span::new(code);
+            let (res, c) = const_parser(code).unwrap();
+            check_consumed(code, res);
+            assert_eq!(c, expect);
+        }
+    }
+
+    #[test]
+    fn test_unsigned_const() {
+        let codes = vec![
+            ("10_u32", Const::new_u32(10)),
+            ("4294967295_u32", Const::new_u32(u32::MAX)),
+            ("0xffff_ffff_u32", Const::new_u32(u32::MAX)),
+            ("10_u64", Const::new_u64(10)),
+            ("18446744073709551615_u64", Const::new_u64(u64::MAX)),
+        ];
+
+        for (code, expect) in codes {
+            let code = Span::new(code);
+            let (res, c) = const_parser(code).unwrap();
+            check_consumed(code, res);
+            assert_eq!(c, expect);
+        }
+
+        // 範囲外の値は型付きのリテラルとしては読めない
+        assert!(const_parser(Span::new("-1_u32")).is_err());
+        let (res, lit) = int_literal_parser(Span::new("-1_u32")).unwrap();
+        assert_eq!(*res.fragment(), "");
+        assert_eq!((lit.value, lit.ty), (-1, Type::UInt32));
+    }
+
+    #[test]
+    fn test_cast() {
+        let codes = vec![
+            ("a as i64", "(a as int64)"),
+            ("a as i64 + b", "((a as int64) + b)"),
+            ("a + b as u32", "(a + (b as uint32))"),
+            ("-a as i64", "((-a) as int64)"),
+            ("a as u64 as i32", "((a as uint64) as int32)"),
+            ("(a + b) as i64 * c", "(((a + b) as int64) * c)"),
+        ];
+
+        for (code, expect) in codes {
+            let code = Span::new(code);
+            let (res, expr) = expr_parser(code).unwrap();
+            check_consumed(code, res);
+            assert_eq!(show_expr(&expr), expect);
+        }
+
+        let err = program_parser(Span::new("fn main(): i64 { return a as ; }")).unwrap_err();
+        assert_eq!(err[0].to_string(), "1:30: expected type, found `;`");
+    }
+
+    #[test]
+    fn test_unary_op() {
+        let codes = vec![
+            ("-a * b", "((-a) * b)"),
+            ("a - -1", "(a - -1)"),
+            ("a--b", "(a - (-b))"),
+            ("!a && b", "((!a) && b)"),
+            ("!(a && b)", "(!(a && b))"),
+            ("~a + 1", "((~a) + 1)"),
+            ("- -a", "(-(-a))"),
+            ("!!a", "(!(!a))"),
+        ];
+
+        for (code, expect) in codes {
+            let code = Span::new(code);
+            let (res, expr) = expr_parser(code).unwrap();
+            check_consumed(code, res);
+            assert_eq!(show_expr(&expr), expect);
+        }
+    }
+
     #[test]
     fn test_binop_add() {
         let IDK = Span::new("");
@@ -461,12 +1223,12 @@ mod tests {
             .collect();
 
         for code in codes {
-            let (res, expr) = or_expr_parser(code).unwrap();
+            let (res, expr) = expr_parser(code).unwrap();
             let expect_expr: Expr = Expr::BinOp(Box::new(BinOp::new(
                 IDK,
-                Expr::Const(Const::new_i32(1)),
+                int(1),
                 Op::Add,
-                Expr::Const(Const::new_i32(2)),
+                int(2),
                 Type::Unknown,
             )));
             // dbg!(&expect_expr);
@@ -485,20 +1247,14 @@ mod tests {
         for code in codes {
             let expect_expr: Expr = Expr::BinOp(Box::new(BinOp::new(
                 IDK,
-                Expr::Const(Const::new_i32(1)), // 1
-                Op::Add,                        // +
                 Expr::BinOp(Box::new(
-                    BinOp::new(
-                        IDK,
-                        Expr::Const(Const::new_i32(2)),
-                        Op::Add,
-                        Expr::Const(Const::new_i32(3)),
-                        Type::Unknown,
-                    ), // 2 + 3
+                    BinOp::new(IDK, int(1), Op::Add, int(2), Type::Unknown), // 1 + 2
                 )),
+                Op::Add, // +
+                int(3),  // 3
                 Type::Unknown,
             )));
-            let (res, expr) = or_expr_parser(code).unwrap();
+            let (res, expr) = expr_parser(code).unwrap();
             check_consumed(code, res);
             assert_eq!(expect_expr, expr);
         }
@@ -515,19 +1271,13 @@ mod tests {
             let expect_expr: Expr = Expr::BinOp(Box::new(BinOp::new(
                 IDK,
                 Expr::BinOp(Box::new(
-                    BinOp::new(
-                        IDK,
-                        Expr::Const(Const::new_i32(1)),
-                        Op::Mul,
-                        Expr::Const(Const::new_i32(2)),
-                        Type::Unknown,
-                    ), // 1 * 2
+                    BinOp::new(IDK, int(1), Op::Mul, int(2), Type::Unknown), // 1 * 2
                 )),
-                Op::Add,                        // +
-                Expr::Const(Const::new_i32(3)), // 3
+                Op::Add, // +
+                int(3),  // 3
                 Type::Unknown,
             )));
-            let (res, expr) = or_expr_parser(code).unwrap();
+            let (res, expr) = expr_parser(code).unwrap();
             check_consumed(code, res);
             assert_eq!(expect_expr, expr);
         }
@@ -545,19 +1295,13 @@ mod tests {
             let expect_expr: Expr = Expr::BinOp(Box::new(BinOp::new(
                 IDK,
                 Expr::BinOp(Box::new(
-                    BinOp::new(
-                        IDK,
-                        Expr::Const(Const::new_i32(1)),
-                        Op::Geq,
-                        Expr::Const(Const::new_i32(2)),
-                        Type::Unknown,
-                    ), // 1 >= 2
+                    BinOp::new(IDK, int(1), Op::Geq, int(2), Type::Unknown), // 1 >= 2
                 )),
-                Op::Eq,                         // ==
-                Expr::Const(Const::new_i32(3)), // 3
+                Op::Eq, // ==
+                int(3), // 3
                 Type::Unknown,
             )));
-            let (res, expr) = or_expr_parser(code).unwrap();
+            let (res, expr) = expr_parser(code).unwrap();
             // dbg!(&expect_expr);
             check_consumed(code, res);
             assert_eq!(expect_expr, expr);
@@ -579,11 +1323,361 @@ mod tests {
         .collect();
 
         for code in codes {
-            let (s, _) = or_expr_parser(code).unwrap();
+            let (s, _) = expr_parser(code).unwrap();
             check_consumed(code, s);
         }
     }
 
+    /// 式を括弧付きの文字列にする
+    fn show_expr(expr: &Expr) -> String {
+        match expr {
+            Expr::Const(Const::I32Const(n)) => n.to_string(),
+            Expr::IntLiteral(lit) => lit.value.to_string(),
+            Expr::Variable(var) => var.id.clone(),
+            Expr::UnaryOp(unary_op) => {
+                let symbol = match unary_op.op {
+                    UnOp::Neg => "-",
+                    UnOp::Not => "!",
+                    UnOp::BitNot => "~",
+                    UnOp::Ref => "&",
+                    UnOp::Deref => "*",
+                };
+                format!("({}{})", symbol, show_expr(&unary_op.expr))
+            }
+            Expr::Cast(cast) => format!("({} as {})", show_expr(&cast.expr), cast.ty),
+            Expr::ArrayLiteral(array) => {
+                let elems: Vec<String> = array.elems.iter().map(show_expr).collect();
+                format!("[{}]", elems.join(", "))
+            }
+            Expr::Index(index) => {
+                format!("({}[{}])", show_expr(&index.array), show_expr(&index.index))
+            }
+            Expr::StructLiteral(literal) => {
+                let fields: Vec<String> = literal
+                    .fields
+                    .iter()
+                    .map(|field| format!("{}: {}", field.id, show_expr(&field.value)))
+                    .collect();
+                format!("{} {{ {} }}", literal.id, fields.join(", "))
+            }
+            Expr::Field(field) => format!("({}.{})", show_expr(&field.expr), field.id),
+            Expr::VariantLiteral(literal) if literal.args.is_empty() => {
+                format!("{}::{}", literal.enum_id, literal.variant)
+            }
+            Expr::VariantLiteral(literal) => {
+                let args: Vec<String> = literal.args.iter().map(show_expr).collect();
+                format!(
+                    "{}::{}({})",
+                    literal.enum_id,
+                    literal.variant,
+                    args.join(", ")
+                )
+            }
+            Expr::Call(call) => {
+                let args: Vec<String> = call.args.iter().map(show_expr).collect();
+                format!("{}({})", call.id, args.join(", "))
+            }
+            Expr::BinOp(bin_op) => {
+                let (symbol, _, _) = BINARY_OPERATORS
+                    .iter()
+                    .find(|(_, op, _)| *op == bin_op.op)
+                    .unwrap();
+                format!(
+                    "({} {} {})",
+                    show_expr(&bin_op.left),
+                    symbol,
+                    show_expr(&bin_op.right)
+                )
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    #[test]
+    fn test_array() {
+        let (_, ty) = type_parser(Span::new("[[i32; 3]; 0x10]")).unwrap();
+        let row = Type::Array(Box::new(Type::Int32), 3);
+        assert_eq!(ty, Type::Array(Box::new(row), 16));
+        assert!(type_parser(Span::new("[i32; -1]")).is_err());
+        assert!(type_parser(Span::new("[i32; 4294967295]")).is_ok());
+        assert!(type_parser(Span::new("[i32; 4294967296]")).is_err());
+        assert!(type_parser(Span::new("[i32]")).is_err());
+
+        let codes = vec![
+            ("[]", "[]"),
+            ("[1, 2, 3,]", "[1, 2, 3]"),
+            ("a[i + 1]", "(a[(i + 1)])"),
+            ("a[i][j]", "((a[i])[j])"),
+            ("-a[0] * b [1]", "((-(a[0])) * (b[1]))"),
+            ("[[1], [2]][0]", "([[1], [2]][0])"),
+        ];
+        for (code, expect) in codes {
+            let code = Span::new(code);
+            let (res, expr) = expr_parser(code).unwrap();
+            check_consumed(code, res);
+            assert_eq!(show_expr(&expr), expect);
+        }
+
+        let code = Span::new("a[i][0] = a[i][1];");
+        let (res, assign) = assign_parser(code).unwrap();
+        check_consumed(code, res);
+        assert_eq!(show_expr(&assign.left), "((a[i])[0])");
+        // 代入できない式
+        assert!(assign_parser(Span::new("f()[0] = 1;")).is_err());
+        assert!(assign_parser(Span::new("1 = 1;")).is_err());
+    }
+
+    #[test]
+    fn test_pointer() {
+        let (_, ty) = type_parser(Span::new("**[i32; 2]")).unwrap();
+        let array = Type::Array(Box::new(Type::Int32), 2);
+        assert_eq!(ty, Type::Pointer(Box::new(Type::Pointer(Box::new(array)))));
+        assert!(type_parser(Span::new("*")).is_err());
+
+        let codes = vec![
+            ("&a", "(&a)"),
+            ("*p + 1", "((*p) + 1)"),
+            ("a * *p", "(a * (*p))"),
+            ("**pp", "(*(*pp))"),
+            ("*p[0]", "(*(p[0]))"),
+            ("&a[1]", "(&(a[1]))"),
+            ("(*p)[1]", "((*p)[1])"),
+        ];
+        for (code, expect) in codes {
+            let code = Span::new(code);
+            let (res, expr) = expr_parser(code).unwrap();
+            check_consumed(code, res);
+            assert_eq!(show_expr(&expr), expect);
+        }
+
+        for (code, expect) in [("*p = 1;", "(*p)"), ("(*p)[i] = 1;", "((*p)[i])")] {
+            let code = Span::new(code);
+            let (res, assign) = assign_parser(code).unwrap();
+            check_consumed(code, res);
+            assert_eq!(show_expr(&assign.left), expect);
+        }
+        assert!(assign_parser(Span::new("&a = 1;")).is_err());
+    }
+
+    #[test]
+    fn test_struct() {
+        let code = Span::new("struct Point {\n    x: i32,\n    next: *Point,\n}");
+        let (res, decl) = struct_decl_parser(code).unwrap();
+        check_consumed(code, res);
+        assert_eq!(decl.id, "Point");
+        let fields: Vec<(&str, Type)> = decl
+            .fields
+            .iter()
+            .map(|field| (field.id.as_str(), field.ty.clone()))
+            .collect();
+        let point = Type::Named("Point".to_owned());
+        assert_eq!(
+            fields,
+            vec![("x", Type::Int32), ("next", Type::Pointer(Box::new(point)))]
+        );
+        let (_, decl) = struct_decl_parser(Span::new("struct Unit {}")).unwrap();
+        assert!(decl.fields.is_empty());
+
+        let codes = vec![
+            ("Point { x: 1, y: 2 + 3 }", "Point { x: 1, y: (2 + 3) }"),
+            ("Point { x: 1, }", "Point { x: 1 }"),
+            ("p.x + p.y", "((p.x) + (p.y))"),
+            ("a[0].p . x", "(((a[0]).p).x)"),
+            ("-(*q).x", "(-((*q).x))"),
+            ("f(1).x", "(f(1).x)"),
+            ("Point { x: 1 }.x", "(Point { x: 1 }.x)"),
+        ];
+        for (code, expect) in codes {
+            let code = Span::new(code);
+            let (res, expr) = expr_parser(code).unwrap();
+            check_consumed(code, res);
+            assert_eq!(show_expr(&expr), expect);
+        }
+
+        let code = Span::new("a[i].x = p.y;");
+        let (res, assign) = assign_parser(code).unwrap();
+        check_consumed(code, res);
+        assert_eq!(show_expr(&assign.left), "((a[i]).x)");
+        assert!(assign_parser(Span::new("f().x = 1;")).is_err());
+
+        let errors = program_parser(Span::new("struct P { x: i32 y: i32 }")).unwrap_err();
+        assert_eq!(errors[0].to_string(), "1:19: expected `}`, found `y`");
+    }
+
+    #[test]
+    fn test_enum() {
+        let code =
+            Span::new("enum Shape {\n    Circle(i32),\n    Rect(i32, *Shape),\n    Empty,\n}");
+        let (res, decl) = enum_decl_parser(code).unwrap();
+        check_consumed(code, res);
+        assert_eq!(decl.id, "Shape");
+        let variants: Vec<(&str, Vec<Type>)> = decl
+            .variants
+            .iter()
+            .map(|variant| (variant.id.as_str(), variant.fields.clone()))
+            .collect();
+        let shape = Type::Pointer(Box::new(Type::Named("Shape".to_owned())));
+        assert_eq!(
+            variants,
+            vec![
+                ("Circle", vec![Type::Int32]),
+                ("Rect", vec![Type::Int32, shape]),
+                ("Empty", vec![]),
+            ]
+        );
+
+        let codes = vec![
+            ("Color::Red", "Color::Red"),
+            ("Shape::Rect(1, 2 + 3)", "Shape::Rect(1, (2 + 3))"),
+        ];
+        for (code, expect) in codes {
+            let (_, expr) = expr_parser(Span::new(code)).unwrap();
+            assert_eq!(show_expr(&expr), expect);
+        }
+
+        let code = Span::new(
+            "match (s) {\n    Shape::Rect(w, _) => { print_i32(w); }\n    Shape::Circle(r) => r,\n    _ => 0\n}",
+        );
+        let (res, stmt) = stmt_parser(code).unwrap();
+        check_consumed(code, res);
+        let m = match stmt {
+            Stmt::Match(m) => m,
+            _ => panic!("expected match, found {:?}", stmt),
+        };
+        assert_eq!(show_expr(&m.expr), "s");
+        assert_eq!(m.arms.len(), 3);
+        match &m.arms[0].pattern {
+            Pattern::Variant(pattern) => {
+                assert_eq!(
+                    (pattern.enum_id.as_str(), pattern.variant.as_str()),
+                    ("Shape", "Rect")
+                );
+                let bindings: Vec<&str> = pattern.bindings.iter().map(|v| v.id.as_str()).collect();
+                assert_eq!(bindings, vec!["w", "_"]);
+            }
+            pattern => panic!("expected variant pattern, found {:?}", pattern),
+        }
+        assert!(matches!(m.arms[0].body, ArmBody::Block(_)));
+        assert!(matches!(m.arms[1].body, ArmBody::Expr(_)));
+        assert!(matches!(m.arms[2].pattern, Pattern::Wildcard(_)));
+
+        let code = Span::new("1 + match (c) { Color::Red => 1, _ => 2 }");
+        let (res, expr) = expr_parser(code).unwrap();
+        check_consumed(code, res);
+        assert!(matches!(expr, Expr::BinOp(_)));
+        // 式の match の腕にはブロックを書けない
+        assert!(expr_parser(Span::new("match (c) { _ => { 1 } }")).is_err());
+        // `match` で始まる名前は呼び出し
+        let (_, expr) = expr_parser(Span::new("matches(1)")).unwrap();
+        assert_eq!(show_expr(&expr), "matches(1)");
+
+        let errors = program_parser(Span::new("enum E { A B }")).unwrap_err();
+        assert_eq!(errors[0].to_string(), "1:12: expected `}`, found `B`");
+        let errors = program_parser(Span::new("fn f(): i32 { return E::1; }")).unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "1:25: expected variant name, found `1`"
+        );
+    }
+
+    #[test]
+    fn test_global() {
+        let code = Span::new("const N: i32 = 10 * 3;");
+        let (res, decl) = global_decl_parser(code).unwrap();
+        check_consumed(code, res);
+        assert_eq!(
+            (decl.id.as_str(), &decl.ty, decl.mutable),
+            ("N", &Type::Int32, false)
+        );
+        assert_eq!(show_expr(&decl.init), "(10 * 3)");
+
+        let code = Span::new("static mut counter: u64 = 0;");
+        let (res, decl) = global_decl_parser(code).unwrap();
+        check_consumed(code, res);
+        assert_eq!(
+            (decl.id.as_str(), &decl.ty, decl.mutable),
+            ("counter", &Type::UInt64, true)
+        );
+
+        let (_, ty) = type_parser(Span::new("[[i32; N]; 2]")).unwrap();
+        let inner = Type::ArrayConstLen(Box::new(Type::Int32), "N".to_owned());
+        assert_eq!(ty, Type::Array(Box::new(inner), 2));
+
+        let codes = vec![
+            ("const N = 1;", "1:9: expected `:`, found `=`"),
+            ("const N: i32;", "1:13: expected `=`, found `;`"),
+            (
+                "static counter: i32 = 0;",
+                "1:8: expected `mut`, found `counter`",
+            ),
+            (
+                "fn f(a: [i32; -1]): unit {}",
+                "1:15: expected array length, found `-`",
+            ),
+        ];
+        for (code, expect) in codes {
+            let errors = program_parser(Span::new(code)).unwrap_err();
+            assert_eq!(errors[0].to_string(), expect, "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_left_assoc() {
+        let codes = vec![
+            ("10 - 3 - 2", "((10 - 3) - 2)"),
+            ("a / b / c", "((a / b) / c)"),
+            ("a % b * c", "((a % b) * c)"),
+            ("a + b - c + d", "(((a + b) - c) + d)"),
+            ("a < b >= c", "((a < b) >= c)"),
+            ("a == b != c", "((a == b) != c)"),
+            ("a && b && c", "((a && b) && c)"),
+            ("a || b || c", "((a || b) || c)"),
+        ];
+
+        for (code, expect) in codes {
+            let code = Span::new(code);
+            let (res, expr) = expr_parser(code).unwrap();
+            check_consumed(code, res);
+            assert_eq!(show_expr(&expr), expect);
+        }
+    }
+
+    #[test]
+    fn test_precedence() {
+        let codes = vec![
+            ("a || b && c", "(a || (b && c))"),
+            ("a && b || c", "((a && b) || c)"),
+            ("a && b == c", "(a && (b == c))"),
+            ("a != b && c", "((a != b) && c)"),
+            ("a == b < c", "(a == (b < c))"),
+            ("a <= b != c", "((a <= b) != c)"),
+            ("a > b + c", "(a > (b + c))"),
+            ("a - b >= c", "((a - b) >= c)"),
+            ("a + b * c", "(a + (b * c))"),
+            ("a / b - c", "((a / b) - c)"),
+            ("(a + b) * c", "((a + b) * c)"),
+            ("a << b + c", "(a << (b + c))"),
+            ("a >> b < c", "((a >> b) < c)"),
+            ("a >= b >> c", "(a >= (b >> c))"),
+            ("a * b >> c - d", "((a * b) >> (c - d))"),
+            (
+                "a || b && c == d < e + f * g",
+                "(a || (b && (c == (d < (e + (f * g))))))",
+            ),
+            (
+                "a * b + c < d == e && f || g",
+                "((((((a * b) + c) < d) == e) && f) || g)",
+            ),
+        ];
+
+        for (code, expect) in codes {
+            let code = Span::new(code);
+            let (res, expr) = expr_parser(code).unwrap();
+            check_consumed(code, res);
+            assert_eq!(show_expr(&expr), expect);
+        }
+    }
+
     #[test]
     fn test_vardecl1() {
         let IDK = Span::new("");
@@ -615,10 +1709,25 @@ mod tests {
             assert_eq!(expect_expr, expr);
         }
     }
+    #[test]
+    fn test_vardecl_infer() {
+        let IDK = Span::new("");
+        let codes: Vec<Span> = vec!["var a = 1;", "var   a=1 ;"]
+            .iter()
+            .map(|code| Span::new(code))
+            .collect();
+        for code in codes {
+            let expect_expr: VariableDecl =
+                VariableDecl::new(IDK, "a".to_owned(), Type::Unknown, Some(int(1)));
+            let (res, expr) = var_decl_parser(code).unwrap();
+            check_consumed(code, res);
+            assert_eq!(expect_expr, expr);
+        }
+    }
 
     #[test]
     fn test_var() {
-        let codes: Vec<Span> = vec!["a", "A", "Ab", "a1", "A123"]
+        let codes: Vec<Span> = vec!["a", "A", "Ab", "a1", "A123", "print_i32", "a_b_"]
             .iter()
             .map(|code| Span::new(code))
             .collect();
@@ -641,16 +1750,10 @@ mod tests {
             let expect_expr: Stmt = Stmt::Expr(Expr::BinOp(Box::new(BinOp::new(
                 IDK,
                 Expr::BinOp(Box::new(
-                    BinOp::new(
-                        IDK,
-                        Expr::Const(Const::new_i32(1)),
-                        Op::Mul,
-                        Expr::Const(Const::new_i32(2)),
-                        Type::Unknown,
-                    ), // 1 * 2
+                    BinOp::new(IDK, int(1), Op::Mul, int(2), Type::Unknown), // 1 * 2
                 )),
-                Op::Add,                        // +
-                Expr::Const(Const::new_i32(3)), // 3
+                Op::Add, // +
+                int(3),  // 3
                 Type::Unknown,
             ))));
 
@@ -673,10 +1776,7 @@ mod tests {
         .map(|code| Span::new(code))
         .collect();
         let exprs: Vec<Stmts> = vec![
-            Stmts::new(vec![
-                Stmt::Expr(Expr::Const(Const::new_i32(1))),
-                Stmt::Expr(Expr::Const(Const::new_i32(2))),
-            ]),
+            Stmts::new(vec![Stmt::Expr(int(1)), Stmt::Expr(int(2))]),
             Stmts::new(vec![
                 Stmt::VariableDecl(VariableDecl::new(IDK, "a".to_owned(), Type::Int32, None)), // var a;
                 Stmt::VariableDecl(VariableDecl::new(IDK, "b".to_owned(), Type::Int32, None)), // var b;
@@ -686,22 +1786,16 @@ mod tests {
                 Stmt::Expr(Expr::BinOp(Box::new(BinOp::new(
                     IDK,
                     Expr::BinOp(Box::new(
-                        BinOp::new(
-                            IDK,
-                            Expr::Const(Const::new_i32(1)),
-                            Op::Mul,
-                            Expr::Const(Const::new_i32(2)),
-                            Type::Unknown,
-                        ), // 1 * 2
+                        BinOp::new(IDK, int(1), Op::Mul, int(2), Type::Unknown), // 1 * 2
                     )),
-                    Op::Add,                        // +
-                    Expr::Const(Const::new_i32(3)), // 3
+                    Op::Add, // +
+                    int(3),  // 3
                     Type::Unknown,
                 )))),
             ]),
             Stmts::new(vec![Stmt::Expr(Expr::BinOp(Box::new(BinOp::new(
                 IDK,
-                Expr::Const(Const::new_i32(1)),
+                int(1),
                 Op::Add,
                 Expr::Variable(Variable::new(Span::new("a"), "a".to_owned(), Type::Unknown)),
                 Type::Unknown,
@@ -759,12 +1853,52 @@ mod tests {
             "h0Ge".to_owned(),
             vec![],
             Type::Unit,
-            Stmts::new(vec![Stmt::Expr(Expr::Const(Const::new_i32(1)))]),
+            Stmts::new(vec![Stmt::Expr(int(1))]),
         );
         let (res, _) = function_decl_parser(code).unwrap();
         check_consumed(code, res);
     }
 
+    #[test]
+    fn test_extern_fn() {
+        let code = Span::new("extern fn print_str(s: string): unit;");
+        let expect = ExternFn::new(
+            code,
+            "print_str".to_owned(),
+            vec![Variable::new(code, "s".to_owned(), Type::String)],
+            Type::Unit,
+            false,
+        );
+        let (res, decl) = extern_fn_parser(code).unwrap();
+        check_consumed(code, res);
+        assert_eq!(decl, expect);
+
+        // 本体は書けない
+        assert!(extern_fn_parser(Span::new("extern fn f(): unit { }")).is_err());
+
+        let code = Span::new("extern fn printf(format: string, ...): i32;");
+        let (res, decl) = extern_fn_parser(code).unwrap();
+        check_consumed(code, res);
+        assert_eq!(decl.args.len(), 1);
+        assert!(decl.variadic);
+    }
+
+    #[test]
+    fn test_program_extern_fn() {
+        let code = Span::new(
+            "extern fn abs(x: i32): i32;\nfn main(): i32 { return abs(-1); }\nextern fn labs(x: i64): i64;",
+        );
+        let program = program_parser(code).unwrap();
+        assert_eq!(program.0.len(), 3);
+        assert_eq!(program.functions().count(), 1);
+        assert!(matches!(&program.0[0], Item::ExternFn(decl) if decl.id == "abs"));
+        assert!(matches!(&program.0[2], Item::ExternFn(decl) if decl.id == "labs"));
+
+        // 壊れた extern fn の後も続きを読む
+        let code = Span::new("extern fn f(x): i32;\nfn main(): i32 { return 1 +; }");
+        assert_eq!(program_parser(code).unwrap_err().len(), 2);
+    }
+
     #[test]
     fn test_call() {
         let codes: Vec<Span> = vec![
@@ -811,8 +1945,14 @@ mod tests {
             .collect();
 
         let expect = Program::new(vec![
-            FunctionDecl::new(IDK, "a".to_owned(), vec![], Type::Unit, Stmts::new(vec![])),
-            FunctionDecl::new(
+            Item::Function(FunctionDecl::new(
+                IDK,
+                "a".to_owned(),
+                vec![],
+                Type::Unit,
+                Stmts::new(vec![]),
+            )),
+            Item::Function(FunctionDecl::new(
                 IDK,
                 "main".to_owned(),
                 vec![],
@@ -822,10 +1962,10 @@ mod tests {
                     "a".to_string(),
                     vec![],
                 )))]),
-            ),
+            )),
         ]);
         for code in codes {
-            let program = program_parser(code);
+            let program = program_parser(code).unwrap();
             dbg!(&program);
             dbg!(&expect);
             assert_eq!(program, expect);
@@ -850,6 +1990,65 @@ mod tests {
             check_consumed(code, rest);
         }
     }
+
+    #[test]
+    fn test_keyword_prefix() {
+        // キーワードで始まる識別子はキーワードにしない
+        let code = Span::new("if (a) { } elsewhere(); whilex = 1; variable(); returned;");
+        let (rest, stmts) = stmts_parser(code).unwrap();
+        check_consumed(code, rest);
+        assert_eq!(stmts.0.len(), 5);
+        match &stmts.0[0] {
+            Stmt::IfElse(if_else) => assert_eq!(if_else.failure, None),
+            stmt => panic!("{:?}", stmt),
+        }
+        assert!(matches!(&stmts.0[1], Stmt::Expr(Expr::Call(call)) if call.id == "elsewhere"));
+        assert!(matches!(stmts.0[2], Stmt::Assign(_)));
+        assert!(matches!(&stmts.0[3], Stmt::Expr(Expr::Call(call)) if call.id == "variable"));
+        assert!(matches!(&stmts.0[4], Stmt::Expr(Expr::Variable(var)) if var.id == "returned"));
+    }
+
+    #[test]
+    fn test_else_if() {
+        let IDK = Span::new("");
+        let code = Span::new("if (a) { 1; } else if (b) { 2; } else if (c) { 3; } else { 4; }");
+        let var = |id: &str| Expr::Variable(Variable::new(IDK, id.to_owned(), Type::Unknown));
+        let num = |n: i128| Stmts::new(vec![Stmt::Expr(int(n))]);
+        let expect = IfElse::new(
+            IDK,
+            var("a"),
+            num(1),
+            Some(Stmts::new(vec![Stmt::IfElse(IfElse::new(
+                IDK,
+                var("b"),
+                num(2),
+                Some(Stmts::new(vec![Stmt::IfElse(IfElse::new(
+                    IDK,
+                    var("c"),
+                    num(3),
+                    Some(num(4)),
+                ))])),
+            ))])),
+        );
+
+        let (rest, if_else) = if_else_parser(code).unwrap();
+        check_consumed(code, rest);
+        assert_eq!(if_else, expect);
+
+        let codes: Vec<Span> = vec![
+            "if (a) { } else if (b) { }",
+            "if(a){}else if(b){}else{}",
+            "if (a) { } else /* b */ if (b) { } else { }",
+        ]
+        .iter()
+        .map(|code| Span::new(code))
+        .collect();
+        for code in codes {
+            let (rest, _) = if_else_parser(code).unwrap();
+            check_consumed(code, rest);
+        }
+    }
+
     #[test]
     fn test_for() {
         let codes: Vec<Span> = vec![
@@ -868,4 +2067,109 @@ mod tests {
             check_consumed(code, rest);
         }
     }
+
+    #[test]
+    fn test_while() {
+        let codes: Vec<Span> = vec![
+            "while(1){}",
+            "while (a < 10) { a = a + 1; }",
+            "while (1) { if (a) { break; } else { continue ; } }",
+        ]
+        .iter()
+        .map(|code| Span::new(code))
+        .collect();
+
+        for code in codes {
+            let (rest, _) = while_parser(code).unwrap();
+            check_consumed(code, rest);
+        }
+    }
+
+    #[test]
+    fn test_break_continue() {
+        let code = Span::new("break; continue; breakfast = 1;");
+        let (rest, stmts) = stmts_parser(code).unwrap();
+        check_consumed(code, rest);
+        let IDK = Span::new("");
+        assert_eq!(stmts.0[0], Stmt::Break(IDK));
+        assert_eq!(stmts.0[1], Stmt::Continue(IDK));
+        if let Stmt::Continue(position) = stmts.0[1] {
+            assert_eq!(position.get_column(), 8);
+        }
+        assert!(matches!(stmts.0[2], Stmt::Assign(_)));
+    }
+
+    #[test]
+    fn test_comments() {
+        let code = Span::new(
+            r#"// entry point
+fn /* name */ main(/* no args */): i32 {
+    var a: i32 = 1; // one
+    /* nested /* comment */ return a; */
+    for (var i: i32 = 0; i < 10; i = i + 1; /* step */) {
+        a = a */* times */ 2;
+    }
+    return a // done
+    ;
+}
+"#,
+        );
+        let program = program_parser(code).unwrap();
+        assert_eq!(program.0.len(), 1);
+        assert_eq!(program.functions().next().unwrap().stmts.0.len(), 3);
+    }
+
+    #[test]
+    fn test_program_errors() {
+        let code = Span::new(
+            r#"fn main(): i32 {
+    var a: i32 = ;
+    return a;
+}
+
+fn f(): unit {
+    f(1;
+    if (1) { 2 }
+}
+"#,
+        );
+        let errors = program_parser(code).unwrap_err();
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "2:18: expected expression, found `;`",
+                "7:8: expected `)`, found `;`",
+                "8:16: expected `;`, found `}`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_program_error_top_level() {
+        let codes: Vec<(Span, &str)> = vec![
+            (
+                "fn main(): i32 { return 0; } }",
+                "1:30: expected `fn`, `extern`, `struct`, `enum`, `const` or `static`, found `}`",
+            ),
+            ("fn main(: i32 {}", "1:9: expected `)`, found `:`"),
+            (
+                "hello",
+                "1:1: expected `fn`, `extern`, `struct`, `enum`, `const` or `static`, found `hello`",
+            ),
+            (
+                "fn main(): unit { /* /* */ }",
+                "1:29: expected `*/`, found end of input",
+            ),
+        ]
+        .into_iter()
+        .map(|(code, msg)| (Span::new(code), msg))
+        .collect();
+
+        for (code, msg) in codes {
+            let errors = program_parser(code).unwrap_err();
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].to_string(), msg);
+        }
+    }
 }
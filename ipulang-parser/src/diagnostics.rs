@@ -0,0 +1,224 @@
+//! コンパイラ全体で使うエラー, 警告
+//!
+//! | code  | 内容                                  |
+//! |-------|---------------------------------------|
+//! | E0001 | 構文エラー                            |
+//! | E0101 | 型が合わない                          |
+//! | E0102 | 変数が見つからない                    |
+//! | E0103 | 関数が見つからない                    |
+//! | E0104 | 引数の数が合わない                    |
+//! | E0105 | 同じ名前の宣言がある                  |
+//! | E0106 | ループの外の break, continue          |
+//! | E0107 | 演算子が使えない型                    |
+//! | E0108 | 値を返さずに関数の終わりに到達する    |
+//! | E0109 | 変換できない型へのキャスト            |
+//! | E0110 | 変数の型が推論できない                |
+//! | E0111 | 整数リテラルが型の範囲に収まらない    |
+//! | E0112 | 定数の添字が配列の範囲外              |
+//! | E0113 | 配列でない値に添字を付けた            |
+//! | E0114 | アドレスを取れない式に `&` を付けた   |
+//! | E0115 | ポインタでない値に `*` を付けた       |
+//! | E0116 | 型が見つからない                      |
+//! | E0117 | 構造体にないフィールド                |
+//! | E0118 | 構造体リテラルのフィールドが足りない  |
+//! | E0119 | 自分自身を値として含む構造体, enum    |
+//! | E0120 | match でバリアントが漏れている        |
+//! | E0121 | enum にないバリアント                 |
+//! | E0122 | enum でない値を match した            |
+//! | E0123 | 定数への代入                          |
+//! | E0124 | コンパイル時に計算できない定数        |
+//! | E0125 | unit 型の変数                         |
+//! | E0126 | 構造体リテラルのフィールドが重複      |
+//! | E0201 | 不正な LLVM IR を生成した             |
+//! | W0001 | 到達できない文                        |
+//! | W0002 | 到達できない match の腕               |
+use std::fmt;
+
+use crate::error::ParseError;
+use crate::nodes::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// ソース中の範囲. ASTより長く持てるように `Span` から切り離しておく
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: u32,
+    pub column: usize,
+    pub offset: usize,
+    /// バイト数
+    pub len: usize,
+}
+
+impl Location {
+    pub fn new(line: u32, column: usize, offset: usize, len: usize) -> Self {
+        Self {
+            line,
+            column,
+            offset,
+            len,
+        }
+    }
+
+    pub fn from_span(span: Span) -> Self {
+        Self::new(
+            span.location_line(),
+            span.get_utf8_column(),
+            span.location_offset(),
+            span.fragment().len(),
+        )
+    }
+}
+
+/// 位置についた説明. primary は `^^^`, secondary は `---` で表示する
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub location: Location,
+    pub message: String,
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code,
+            message: message.into(),
+            labels: vec![],
+            notes: vec![],
+            help: None,
+        }
+    }
+
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, code, message)
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, code, message)
+    }
+
+    pub fn with_label(
+        mut self,
+        location: Location,
+        message: impl Into<String>,
+        primary: bool,
+    ) -> Self {
+        self.labels.push(Label {
+            location,
+            message: message.into(),
+            primary,
+        });
+        self
+    }
+
+    pub fn with_primary(self, span: Span, message: impl Into<String>) -> Self {
+        self.with_label(Location::from_span(span), message, true)
+    }
+
+    pub fn with_secondary(self, span: Span, message: impl Into<String>) -> Self {
+        self.with_label(Location::from_span(span), message, false)
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// 主な位置
+    pub fn primary(&self) -> Option<&Label> {
+        self.labels.iter().find(|label| label.primary)
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// 1行で表示する. ソースを添えて表示するのは ipulang-compiler の仕事
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(label) = self.primary().or_else(|| self.labels.first()) {
+            write!(f, "{}:{}: ", label.location.line, label.location.column)?;
+        }
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+impl From<ParseError> for Diagnostic {
+    fn from(e: ParseError) -> Self {
+        // `found` は "`tok`" か "end of input"
+        let len = if e.found.starts_with('`') {
+            e.found.len() - 2
+        } else {
+            0
+        };
+        let location = Location::new(e.line, e.column, e.offset, len);
+        Diagnostic::error("E0001", e.message()).with_label(
+            location,
+            format!("expected {}", e.expected_list()),
+            true,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_parse_error() {
+        let code = Span::new("var a: i32 = ;");
+        let e = ParseError::new(nom::Slice::slice(&code, 13..), "expression");
+        let diag = Diagnostic::from(e);
+        assert_eq!(
+            diag.to_string(),
+            "1:14: error[E0001]: expected expression, found `;`"
+        );
+        let label = diag.primary().unwrap();
+        assert_eq!(label.location, Location::new(1, 14, 13, 1));
+        assert_eq!(label.message, "expected expression");
+    }
+
+    #[test]
+    fn test_labels() {
+        let code = Span::new("a + b");
+        let diag = Diagnostic::warning("W0001", "something")
+            .with_secondary(nom::Slice::slice(&code, 0..1), "left")
+            .with_primary(nom::Slice::slice(&code, 4..5), "right")
+            .with_note("note")
+            .with_help("help");
+        assert_eq!(diag.to_string(), "1:5: warning[W0001]: something");
+        assert_eq!(diag.primary().unwrap().message, "right");
+        assert_eq!(diag.labels.len(), 2);
+    }
+}
@@ -0,0 +1,129 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use nom::error::{ContextError, ErrorKind};
+
+use crate::nodes::Span;
+
+pub type IResult<I, O> = nom::IResult<I, O, ParseError>;
+
+/// 構文エラー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: u32,
+    pub column: usize,
+    pub offset: usize,
+    /// 期待していたもの. `alt` で失敗した時は複数になる
+    pub expected: Vec<String>,
+    /// 実際にあったもの
+    pub found: String,
+}
+
+impl ParseError {
+    pub fn new(pos: Span, expected: impl Into<String>) -> Self {
+        Self {
+            line: pos.location_line(),
+            column: pos.get_utf8_column(),
+            offset: pos.location_offset(),
+            expected: vec![expected.into()],
+            found: found_token(pos),
+        }
+    }
+}
+
+/// エラー位置にある字句を表示用に切り出す
+fn found_token(pos: Span) -> String {
+    let rest = pos.fragment();
+    let word: String = rest
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    if !word.is_empty() {
+        format!("`{}`", word)
+    } else if let Some(c) = rest.chars().next() {
+        format!("`{}`", c)
+    } else {
+        "end of input".to_owned()
+    }
+}
+
+fn describe_kind(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::Digit => "number",
+        ErrorKind::Alpha | ErrorKind::AlphaNumeric => "identifier",
+        ErrorKind::MultiSpace | ErrorKind::Space => "whitespace",
+        ErrorKind::Eof => "end of input",
+        _ => "token",
+    }
+}
+
+impl<'a> nom::error::ParseError<Span<'a>> for ParseError {
+    fn from_error_kind(input: Span<'a>, kind: ErrorKind) -> Self {
+        ParseError::new(input, describe_kind(kind))
+    }
+
+    fn append(_: Span<'a>, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn from_char(input: Span<'a>, c: char) -> Self {
+        ParseError::new(input, format!("`{}`", c))
+    }
+
+    /// より先まで読めた方のエラーを残す. 同じ位置なら期待していたものをまとめる
+    fn or(mut self, other: Self) -> Self {
+        match self.offset.cmp(&other.offset) {
+            Ordering::Greater => self,
+            Ordering::Less => other,
+            Ordering::Equal => {
+                for expected in other.expected {
+                    if !self.expected.contains(&expected) {
+                        self.expected.push(expected);
+                    }
+                }
+                self
+            }
+        }
+    }
+}
+
+impl<'a> ContextError<Span<'a>> for ParseError {
+    /// 読み始めの位置で失敗した時だけ `ctx` を期待していたものにする
+    fn add_context(input: Span<'a>, ctx: &'static str, other: Self) -> Self {
+        if input.location_offset() == other.offset {
+            ParseError::new(input, ctx)
+        } else {
+            other
+        }
+    }
+}
+
+impl ParseError {
+    /// `A, B or C`
+    pub fn expected_list(&self) -> String {
+        let mut list = String::new();
+        for (i, expected) in self.expected.iter().enumerate() {
+            if i > 0 {
+                let sep = if i + 1 == self.expected.len() {
+                    " or "
+                } else {
+                    ", "
+                };
+                list.push_str(sep);
+            }
+            list.push_str(expected);
+        }
+        list
+    }
+
+    /// 位置を除いたメッセージ
+    pub fn message(&self) -> String {
+        format!("expected {}, found {}", self.expected_list(), self.found)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message())
+    }
+}
@@ -1,3 +1,7 @@
 pub mod ast;
+pub mod diagnostics;
+pub mod error;
 pub mod nodes;
+pub mod prelude;
+pub mod trivia;
 pub mod types;
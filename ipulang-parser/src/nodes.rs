@@ -20,14 +20,29 @@ pub enum Op {
     Mul, // *
     Div, // /
     Mod, // %
+    Shl, // <<
+    Shr, // >>
 }
 
-/// 定数
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Neg,    // -
+    Not,    // !
+    BitNot, // ~
+    Ref,    // &
+    Deref,  // *
+}
+
+/// 定数
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Const {
     I32Const(i32),
     I64Const(i64),
+    U32Const(u32),
+    U64Const(u64),
     BoolConst(bool),
+    /// エスケープを解決した後の文字列
+    Str(String),
 }
 
 impl Const {
@@ -39,9 +54,32 @@ impl Const {
         Const::I64Const(val)
     }
 
+    pub fn new_u32(val: u32) -> Const {
+        Const::U32Const(val)
+    }
+
+    pub fn new_u64(val: u64) -> Const {
+        Const::U64Const(val)
+    }
+
     pub fn new_bool(val: bool) -> Const {
         Const::BoolConst(val)
     }
+
+    pub fn new_str(val: impl Into<String>) -> Const {
+        Const::Str(val.into())
+    }
+
+    pub fn ty(&self) -> Type {
+        match self {
+            Const::I32Const(_) => Type::Int32,
+            Const::I64Const(_) => Type::Int64,
+            Const::U32Const(_) => Type::UInt32,
+            Const::U64Const(_) => Type::UInt64,
+            Const::BoolConst(_) => Type::Bool,
+            Const::Str(_) => Type::String,
+        }
+    }
 }
 
 #[derive(Derivative)]
@@ -67,13 +105,356 @@ impl<'a> BinOp<'a> {
     }
 }
 
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq, Eq)]
+pub struct UnaryOp<'a> {
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub op: UnOp,
+    pub expr: Expr<'a>,
+    pub ty: Type,
+}
+
+impl<'a> UnaryOp<'a> {
+    pub fn new(position: Span<'a>, op: UnOp, expr: Expr<'a>, ty: Type) -> Self {
+        Self {
+            position,
+            op,
+            expr,
+            ty,
+        }
+    }
+}
+
+/// `expr as ty`
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq, Eq)]
+pub struct Cast<'a> {
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub expr: Expr<'a>,
+    /// 変換先の型
+    pub ty: Type,
+}
+
+impl<'a> Cast<'a> {
+    pub fn new(position: Span<'a>, expr: Expr<'a>, ty: Type) -> Self {
+        Self { position, expr, ty }
+    }
+}
+
+/// 型を書かない整数リテラル. 型は型検査で文脈から決める
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq, Eq)]
+pub struct IntLiteral<'a> {
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    /// 符号を含めた値. どの整数型に入るかは型検査で調べる
+    pub value: i128,
+    pub ty: Type,
+}
+
+impl<'a> IntLiteral<'a> {
+    pub fn new(position: Span<'a>, value: i128) -> Self {
+        Self {
+            position,
+            value,
+            ty: Type::Unknown,
+        }
+    }
+}
+
+/// 配列リテラル. `[1, 2, 3]`
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq, Eq)]
+pub struct ArrayLiteral<'a> {
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub elems: Vec<Expr<'a>>,
+    pub ty: Type,
+}
+
+impl<'a> ArrayLiteral<'a> {
+    pub fn new(position: Span<'a>, elems: Vec<Expr<'a>>) -> Self {
+        Self {
+            position,
+            elems,
+            ty: Type::Unknown,
+        }
+    }
+}
+
+/// 添字. `a[i]`
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq, Eq)]
+pub struct Index<'a> {
+    /// `[` の位置
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub array: Expr<'a>,
+    pub index: Expr<'a>,
+    /// 要素の型
+    pub ty: Type,
+}
+
+impl<'a> Index<'a> {
+    pub fn new(position: Span<'a>, array: Expr<'a>, index: Expr<'a>) -> Self {
+        Self {
+            position,
+            array,
+            index,
+            ty: Type::Unknown,
+        }
+    }
+}
+
+/// 構造体リテラルのフィールド. `x: 1`
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq, Eq)]
+pub struct FieldInit<'a> {
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub id: String,
+    pub value: Expr<'a>,
+}
+
+impl<'a> FieldInit<'a> {
+    pub fn new(position: Span<'a>, id: String, value: Expr<'a>) -> Self {
+        Self {
+            position,
+            id,
+            value,
+        }
+    }
+}
+
+/// 構造体リテラル. `Point { x: 1, y: 2 }`
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq, Eq)]
+pub struct StructLiteral<'a> {
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    /// 構造体名
+    pub id: String,
+    pub fields: Vec<FieldInit<'a>>,
+    pub ty: Type,
+}
+
+impl<'a> StructLiteral<'a> {
+    pub fn new(position: Span<'a>, id: String, fields: Vec<FieldInit<'a>>) -> Self {
+        Self {
+            position,
+            id,
+            fields,
+            ty: Type::Unknown,
+        }
+    }
+}
+
+/// フィールドの読み書き. `p.x`
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq, Eq)]
+pub struct Field<'a> {
+    /// `.` の位置
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub expr: Expr<'a>,
+    /// フィールド名
+    pub id: String,
+    pub ty: Type,
+}
+
+impl<'a> Field<'a> {
+    pub fn new(position: Span<'a>, expr: Expr<'a>, id: String) -> Self {
+        Self {
+            position,
+            expr,
+            id,
+            ty: Type::Unknown,
+        }
+    }
+}
+
+/// enum のバリアントの値. `Shape::Rect(1, 2)`, `Color::Red`
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq, Eq)]
+pub struct VariantLiteral<'a> {
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub enum_id: String,
+    pub variant: String,
+    pub args: Vec<Expr<'a>>,
+    pub ty: Type,
+}
+
+impl<'a> VariantLiteral<'a> {
+    pub fn new(position: Span<'a>, enum_id: String, variant: String, args: Vec<Expr<'a>>) -> Self {
+        Self {
+            position,
+            enum_id,
+            variant,
+            args,
+            ty: Type::Unknown,
+        }
+    }
+}
+
+/// バリアントのパターン. `Shape::Rect(w, h)`. 使わないフィールドは `_` にする
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq)]
+pub struct VariantPattern<'a> {
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub enum_id: String,
+    pub variant: String,
+    /// フィールドを入れる変数. 型は型検査で決める
+    pub bindings: Vec<Variable<'a>>,
+}
+
+impl<'a> VariantPattern<'a> {
+    pub fn new(
+        position: Span<'a>,
+        enum_id: String,
+        variant: String,
+        bindings: Vec<Variable<'a>>,
+    ) -> Self {
+        Self {
+            position,
+            enum_id,
+            variant,
+            bindings,
+        }
+    }
+}
+
+/// match の腕のパターン
+#[derive(Debug, PartialEq)]
+pub enum Pattern<'a> {
+    /// `_`. 何にでも当てはまる
+    Wildcard(Span<'a>),
+    Variant(VariantPattern<'a>),
+}
+
+impl<'a> Pattern<'a> {
+    pub fn position(&self) -> Span<'a> {
+        match self {
+            Pattern::Wildcard(position) => *position,
+            Pattern::Variant(pattern) => pattern.position,
+        }
+    }
+}
+
+/// match の腕の中身. 式として使う match では `Expr` だけ
+#[derive(Debug, PartialEq)]
+pub enum ArmBody<'a> {
+    Block(Stmts<'a>),
+    Expr(Expr<'a>),
+}
+
+/// `Shape::Rect(w, h) => w * h`
+#[derive(Debug, PartialEq)]
+pub struct MatchArm<'a> {
+    pub pattern: Pattern<'a>,
+    pub body: ArmBody<'a>,
+}
+
+impl<'a> MatchArm<'a> {
+    pub fn new(pattern: Pattern<'a>, body: ArmBody<'a>) -> Self {
+        Self { pattern, body }
+    }
+}
+
+/// `match (expr) { ... }`. 文としても式としても書ける
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq)]
+pub struct Match<'a> {
+    /// `match` の位置
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub expr: Expr<'a>,
+    pub arms: Vec<MatchArm<'a>>,
+    /// 式として使う時の値の型. 文の時は `Type::Unit`
+    pub ty: Type,
+}
+
+impl<'a> Match<'a> {
+    pub fn new(position: Span<'a>, expr: Expr<'a>, arms: Vec<MatchArm<'a>>) -> Self {
+        Self {
+            position,
+            expr,
+            arms,
+            ty: Type::Unknown,
+        }
+    }
+}
+
 /// 式
 #[derive(Debug, PartialEq)]
 pub enum Expr<'a> {
     Const(Const),
+    IntLiteral(IntLiteral<'a>),
     Variable(Variable<'a>),
     BinOp(Box<BinOp<'a>>),
+    UnaryOp(Box<UnaryOp<'a>>),
+    Cast(Box<Cast<'a>>),
     Call(Call<'a>),
+    ArrayLiteral(ArrayLiteral<'a>),
+    Index(Box<Index<'a>>),
+    StructLiteral(StructLiteral<'a>),
+    Field(Box<Field<'a>>),
+    VariantLiteral(VariantLiteral<'a>),
+    Match(Box<Match<'a>>),
+}
+
+impl<'a> Expr<'a> {
+    /// エラー表示に使う位置. 二項演算は演算子の位置
+    pub fn position(&self) -> Option<Span<'a>> {
+        match self {
+            Expr::Const(_) => None,
+            Expr::IntLiteral(lit) => Some(lit.position),
+            Expr::Variable(var) => Some(var.position),
+            Expr::BinOp(bin_op) => Some(bin_op.position),
+            Expr::UnaryOp(unary_op) => Some(unary_op.position),
+            Expr::Cast(cast) => Some(cast.position),
+            Expr::Call(call) => Some(call.position),
+            Expr::ArrayLiteral(array) => Some(array.position),
+            Expr::Index(index) => Some(index.position),
+            Expr::StructLiteral(literal) => Some(literal.position),
+            Expr::Field(field) => Some(field.position),
+            Expr::VariantLiteral(literal) => Some(literal.position),
+            Expr::Match(m) => Some(m.position),
+        }
+    }
+
+    /// 代入先やアドレスを取る対象になれる式か. 変数, その要素やフィールド, `*p`
+    pub fn is_place(&self) -> bool {
+        match self {
+            Expr::Variable(_) => true,
+            Expr::Index(index) => index.array.is_place(),
+            Expr::Field(field) => field.expr.is_place(),
+            Expr::UnaryOp(unary_op) => unary_op.op == UnOp::Deref,
+            _ => false,
+        }
+    }
+
+    /// 型検査で決まった型. 検査前は `Type::Unknown`
+    pub fn ty(&self) -> Type {
+        match self {
+            Expr::Const(c) => c.ty(),
+            Expr::IntLiteral(lit) => lit.ty.clone(),
+            Expr::Variable(var) => var.ty.clone(),
+            Expr::BinOp(bin_op) => bin_op.ty.clone(),
+            Expr::UnaryOp(unary_op) => unary_op.ty.clone(),
+            Expr::Cast(cast) => cast.ty.clone(),
+            Expr::Call(call) => call.ty.clone(),
+            Expr::ArrayLiteral(array) => array.ty.clone(),
+            Expr::Index(index) => index.ty.clone(),
+            Expr::StructLiteral(literal) => literal.ty.clone(),
+            Expr::Field(field) => field.ty.clone(),
+            Expr::VariantLiteral(literal) => literal.ty.clone(),
+            Expr::Match(m) => m.ty.clone(),
+        }
+    }
 }
 
 #[derive(Derivative)]
@@ -149,6 +530,37 @@ impl<'a> FunctionDecl<'a> {
     }
 }
 
+/// 本体のない関数宣言. `extern fn putchar(c: i32): i32;`
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq)]
+pub struct ExternFn<'a> {
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub id: String,
+    pub args: Vec<Variable<'a>>,
+    pub ret_typ: Type,
+    /// 引数の最後が `...` かどうか. `extern fn printf(format: string, ...): i32;`
+    pub variadic: bool,
+}
+
+impl<'a> ExternFn<'a> {
+    pub fn new(
+        position: Span<'a>,
+        id: String,
+        args: Vec<Variable<'a>>,
+        ret_typ: Type,
+        variadic: bool,
+    ) -> Self {
+        Self {
+            position,
+            id,
+            args,
+            ret_typ,
+            variadic,
+        }
+    }
+}
+
 #[derive(Derivative)]
 #[derivative(Debug, PartialEq)]
 pub struct Call<'a> {
@@ -156,11 +568,18 @@ pub struct Call<'a> {
     pub position: Span<'a>,
     pub id: String,
     pub args: Vec<Expr<'a>>,
+    /// 戻り値の型
+    pub ty: Type,
 }
 
 impl<'a> Call<'a> {
     pub fn new(position: Span<'a>, id: String, args: Vec<Expr<'a>>) -> Self {
-        Self { position, id, args }
+        Self {
+            position,
+            id,
+            args,
+            ty: Type::Unknown,
+        }
     }
 }
 
@@ -219,7 +638,27 @@ impl<'a> For<'a> {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq)]
+pub struct While<'a> {
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub cond: Expr<'a>,
+    pub stmts: Stmts<'a>,
+}
+
+impl<'a> While<'a> {
+    pub fn new(position: Span<'a>, cond: Expr<'a>, stmts: Stmts<'a>) -> Self {
+        Self {
+            position,
+            cond,
+            stmts,
+        }
+    }
+}
+
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq)]
 pub enum Stmt<'a> {
     Expr(Expr<'a>),
     Return(Expr<'a>),
@@ -227,14 +666,178 @@ pub enum Stmt<'a> {
     Assign(Assign<'a>),
     IfElse(IfElse<'a>),
     For(For<'a>),
+    While(While<'a>),
+    Match(Match<'a>),
+    Break(#[derivative(PartialEq = "ignore")] Span<'a>),
+    Continue(#[derivative(PartialEq = "ignore")] Span<'a>),
+}
+
+/// 構造体の宣言. `struct Point { x: i32, y: i32 }`
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq)]
+pub struct StructDecl<'a> {
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub id: String,
+    /// 宣言した順に並ぶ
+    pub fields: Vec<Variable<'a>>,
+}
+
+impl<'a> StructDecl<'a> {
+    pub fn new(position: Span<'a>, id: String, fields: Vec<Variable<'a>>) -> Self {
+        Self {
+            position,
+            id,
+            fields,
+        }
+    }
+}
+
+/// enum のバリアント. `Rect(i32, i32)`, `Red`
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq)]
+pub struct Variant<'a> {
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub id: String,
+    /// 持っている値の型. C のような enum では空
+    pub fields: Vec<Type>,
+}
+
+impl<'a> Variant<'a> {
+    pub fn new(position: Span<'a>, id: String, fields: Vec<Type>) -> Self {
+        Self {
+            position,
+            id,
+            fields,
+        }
+    }
+}
+
+/// enum の宣言. `enum Shape { Circle(i32), Rect(i32, i32), Empty }`
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq)]
+pub struct EnumDecl<'a> {
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub id: String,
+    /// 宣言した順に並ぶ. 順番が discriminant になる
+    pub variants: Vec<Variant<'a>>,
+}
+
+impl<'a> EnumDecl<'a> {
+    pub fn new(position: Span<'a>, id: String, variants: Vec<Variant<'a>>) -> Self {
+        Self {
+            position,
+            id,
+            variants,
+        }
+    }
+}
+
+/// グローバルな定数 `const N: i32 = 30;` か変数 `static mut n: i32 = 0;`
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq)]
+pub struct GlobalDecl<'a> {
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub id: String,
+    pub ty: Type,
+    pub init: Expr<'a>,
+    /// `static mut` なら true. `const` は書き換えられない
+    pub mutable: bool,
+    /// 型検査で計算した初期値. bool は 0 か 1
+    pub value: i128,
+}
+
+impl<'a> GlobalDecl<'a> {
+    pub fn new(position: Span<'a>, id: String, ty: Type, init: Expr<'a>, mutable: bool) -> Self {
+        Self {
+            position,
+            id,
+            ty,
+            init,
+            mutable,
+            value: 0,
+        }
+    }
+}
+
+/// トップレベルに書けるもの
+#[derive(Debug, PartialEq)]
+pub enum Item<'a> {
+    Function(FunctionDecl<'a>),
+    ExternFn(ExternFn<'a>),
+    Struct(StructDecl<'a>),
+    Enum(EnumDecl<'a>),
+    Global(GlobalDecl<'a>),
 }
 
 #[derive(Debug, PartialEq)]
-pub struct Program<'a>(pub Vec<FunctionDecl<'a>>);
+pub struct Program<'a>(pub Vec<Item<'a>>);
 
 impl<'a> Program<'a> {
-    pub fn new(functions: Vec<FunctionDecl<'a>>) -> Self {
-        Self(functions)
+    pub fn new(items: Vec<Item<'a>>) -> Self {
+        Self(items)
+    }
+
+    /// 本体のある関数宣言
+    pub fn functions(&self) -> impl Iterator<Item = &FunctionDecl<'a>> {
+        self.0.iter().filter_map(|item| match item {
+            Item::Function(function) => Some(function),
+            _ => None,
+        })
+    }
+
+    /// 構造体の宣言
+    pub fn structs(&self) -> impl Iterator<Item = &StructDecl<'a>> {
+        self.0.iter().filter_map(|item| match item {
+            Item::Struct(decl) => Some(decl),
+            _ => None,
+        })
+    }
+
+    /// enum の宣言
+    pub fn enums(&self) -> impl Iterator<Item = &EnumDecl<'a>> {
+        self.0.iter().filter_map(|item| match item {
+            Item::Enum(decl) => Some(decl),
+            _ => None,
+        })
+    }
+
+    /// const と static の宣言
+    pub fn globals(&self) -> impl Iterator<Item = &GlobalDecl<'a>> {
+        self.0.iter().filter_map(|item| match item {
+            Item::Global(decl) => Some(decl),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentKind {
+    Line,  // // ...
+    Block, // /* ... */
+}
+
+/// コメント. ASTには入れずに位置と一緒に別で持っておく
+#[derive(Derivative)]
+#[derivative(Debug, PartialEq)]
+pub struct Comment<'a> {
+    #[derivative(PartialEq = "ignore")]
+    pub position: Span<'a>,
+    pub kind: CommentKind,
+    /// `//`, `/*`, `*/` を除いた中身
+    pub text: String,
+}
+
+impl<'a> Comment<'a> {
+    pub fn new(position: Span<'a>, kind: CommentKind, text: String) -> Self {
+        Self {
+            position,
+            kind,
+            text,
+        }
     }
 }
 
@@ -243,12 +846,13 @@ impl<'a> Program<'a> {
 pub struct Assign<'a> {
     #[derivative(PartialEq = "ignore")]
     pub position: Span<'a>,
-    pub left: String,
+    /// 代入先. 変数か `a[i]`
+    pub left: Expr<'a>,
     pub right: Expr<'a>,
 }
 
 impl<'a> Assign<'a> {
-    pub fn new(position: Span<'a>, left: String, right: Expr<'a>) -> Self {
+    pub fn new(position: Span<'a>, left: Expr<'a>, right: Expr<'a>) -> Self {
         Self {
             position,
             left,
@@ -0,0 +1,56 @@
+//! 組み込み関数の宣言. ipulang の `extern fn` で書いておき, 型検査もコード生成もここから宣言を作る
+//! 組み込み関数を足すときは `PRELUDE` に書く
+use nom::{
+    combinator::eof,
+    multi::many0,
+    sequence::{preceded, terminated},
+};
+
+use crate::ast::extern_fn_parser;
+use crate::nodes::{ExternFn, Span};
+use crate::trivia::trivia0;
+
+pub const PRELUDE: &str = r#"
+// libc
+extern fn putchar(c: i32): i32;
+extern fn getchar(): i32;
+extern fn abort(): unit;
+
+// ランタイム. 本体は ipulang-codegen が putchar, getchar を使って作る
+extern fn print_i32(n: i32): unit;
+extern fn print_i64(n: i64): unit;
+extern fn print_bool(b: bool): unit;
+extern fn print_str(s: string): unit;
+// 改行を出力する
+extern fn println(): unit;
+// 空白を読み飛ばして10進数の整数を読む
+extern fn read_i32(): i32;
+"#;
+
+/// `PRELUDE` の宣言の一覧
+pub fn prelude() -> Vec<ExternFn<'static>> {
+    let (_, decls) = terminated(
+        many0(preceded(trivia0, extern_fn_parser)),
+        preceded(trivia0, eof),
+    )(Span::new(PRELUDE))
+    .expect("PRELUDE should be valid");
+    decls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Type;
+
+    #[test]
+    fn test_prelude() {
+        let decls = prelude();
+        assert_eq!(decls.len(), 9);
+        assert_eq!(decls[0].id, "putchar");
+        assert_eq!(decls[0].args[0].ty, Type::Int32);
+        assert_eq!(decls[0].ret_typ, Type::Int32);
+        assert_eq!(decls[6].id, "print_str");
+        assert_eq!(decls[6].args[0].ty, Type::String);
+        assert_eq!(decls[6].ret_typ, Type::Unit);
+    }
+}
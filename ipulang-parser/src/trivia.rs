@@ -0,0 +1,184 @@
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{multispace1, not_line_ending},
+    combinator::{consumed, map},
+    multi::{many0, many1},
+    sequence::preceded,
+    Slice,
+};
+
+use crate::error::{IResult, ParseError};
+use crate::nodes::{Comment, CommentKind, Span};
+
+/// `// ...`
+pub fn line_comment_parser(s: Span) -> IResult<Span, Comment> {
+    map(
+        consumed(preceded(tag("//"), not_line_ending)),
+        |(pos, text): (Span, Span)| {
+            Comment::new(pos, CommentKind::Line, text.fragment().to_string())
+        },
+    )(s)
+}
+
+/// `/* ... */`. 入れ子にできる
+pub fn block_comment_parser(s: Span) -> IResult<Span, Comment> {
+    let (body, _) = tag("/*")(s)?;
+    let fragment = *body.fragment();
+    let bytes = fragment.as_bytes();
+    let mut depth = 1;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(b"/*") {
+            depth += 1;
+            i += 2;
+        } else if bytes[i..].starts_with(b"*/") {
+            depth -= 1;
+            if depth == 0 {
+                let pos = s.slice(..i + 4);
+                let comment = Comment::new(pos, CommentKind::Block, fragment[..i].to_owned());
+                return Ok((body.slice(i + 2..), comment));
+            }
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    // 閉じていないコメントは読み直しても直らない
+    Err(nom::Err::Failure(ParseError::new(
+        body.slice(fragment.len()..),
+        "`*/`",
+    )))
+}
+
+pub fn comment_parser(s: Span) -> IResult<Span, Comment> {
+    alt((line_comment_parser, block_comment_parser))(s)
+}
+
+/// 空白とコメントを読み飛ばす. 読んだコメントを返す
+pub fn trivia0(s: Span) -> IResult<Span, Vec<Comment>> {
+    map(
+        many0(alt((map(multispace1, |_| None), map(comment_parser, Some)))),
+        |comments| comments.into_iter().flatten().collect(),
+    )(s)
+}
+
+/// 1つ以上の空白かコメント
+pub fn trivia1(s: Span) -> IResult<Span, Vec<Comment>> {
+    map(
+        many1(alt((map(multispace1, |_| None), map(comment_parser, Some)))),
+        |comments| comments.into_iter().flatten().collect(),
+    )(s)
+}
+
+/// 文字列, 文字リテラルを読み飛ばす. 中の `//` や `;` をコメントや区切りと間違えないようにする
+/// 閉じていない時は行末まで読み飛ばす
+pub fn skip_quoted(s: Span) -> Option<Span> {
+    let fragment = *s.fragment();
+    let quote = fragment
+        .chars()
+        .next()
+        .filter(|c| *c == '"' || *c == '\'')?;
+    let mut chars = fragment.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c == quote || c == '\n' {
+            return Some(s.slice(i + c.len_utf8()..));
+        }
+    }
+    Some(s.slice(fragment.len()..))
+}
+
+/// ソース中のコメントを位置付きで全て集める
+/// (ASTには入らないので, formatterやLSPはこれを使ってノードに付け直す)
+pub fn collect_comments(s: Span) -> Vec<Comment> {
+    let mut comments = vec![];
+    let mut s = s;
+    while let Some(c) = s.fragment().chars().next() {
+        if let Ok((rest, comment)) = comment_parser(s) {
+            comments.push(comment);
+            s = rest;
+        } else if let Some(rest) = skip_quoted(s) {
+            s = rest;
+        } else {
+            s = s.slice(c.len_utf8()..);
+        }
+    }
+    comments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_comment() {
+        let code = Span::new("// hello\n1");
+        let (rest, comment) = line_comment_parser(code).unwrap();
+        assert_eq!(*rest.fragment(), "\n1");
+        assert_eq!(comment.kind, CommentKind::Line);
+        assert_eq!(comment.text, " hello");
+        assert_eq!(*comment.position.fragment(), "// hello");
+    }
+
+    #[test]
+    fn test_block_comment() {
+        let codes = vec![
+            ("/* a */", " a "),
+            ("/**/", ""),
+            ("/* a /* b */ c */", " a /* b */ c "),
+            ("/* 1\n 2 */", " 1\n 2 "),
+            ("/* / * */", " / * "),
+        ];
+
+        for (code, text) in codes {
+            let (rest, comment) = block_comment_parser(Span::new(code)).unwrap();
+            assert_eq!(*rest.fragment(), "");
+            assert_eq!(comment.kind, CommentKind::Block);
+            assert_eq!(comment.text, text);
+            assert_eq!(*comment.position.fragment(), code);
+        }
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let res = block_comment_parser(Span::new("/* /* */"));
+        assert!(matches!(res, Err(nom::Err::Failure(_))));
+    }
+
+    #[test]
+    fn test_trivia() {
+        let codes = vec![
+            "",
+            " ",
+            "// a",
+            "  // a\n  /* b */ ",
+            "/* a */// b\n\n/* c /* d */ */",
+        ];
+
+        for code in codes {
+            let (rest, _) = trivia0(Span::new(code)).unwrap();
+            assert_eq!(*rest.fragment(), "");
+        }
+        assert!(trivia1(Span::new("a")).is_err());
+        assert!(trivia1(Span::new("/**/a")).is_ok());
+    }
+
+    #[test]
+    fn test_collect_comments() {
+        let code = Span::new("fn main(): unit { // a\n    /* b\n */ 1; // c\n}");
+        let comments = collect_comments(code);
+        let texts: Vec<(&str, u32)> = comments
+            .iter()
+            .map(|c| (c.text.as_str(), c.position.location_line()))
+            .collect();
+        assert_eq!(texts, vec![(" a", 1), (" b\n ", 2), (" c", 3)]);
+
+        // 文字列の中はコメントではない
+        let code = Span::new(r#"print("// a /* b"); '"'; "\" // c"; // d"#);
+        let comments = collect_comments(code);
+        let texts: Vec<&str> = comments.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec![" d"]);
+    }
+}
@@ -1,13 +1,51 @@
 use std::fmt;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Type {
     Unknown,
     Int32,
     Int64,
+    UInt32,
+    UInt64,
     String,
     Bool,
     Unit,
+    /// 固定長の配列. `[i32; 16]`
+    Array(Box<Type>, u64),
+    /// 長さを定数の名前で書いた配列. `[i32; N]`. 型検査で `Array` にする
+    ArrayConstLen(Box<Type>, String),
+    /// ポインタ. `*i32`
+    Pointer(Box<Type>),
+    /// 名前で参照する型. 構造体か enum
+    Named(String),
+    /// 型エラーになった式の型. これを含む検査では新たなエラーを出さない
+    Error,
+}
+
+impl Type {
+    /// 整数型かどうか
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Type::Int32 | Type::Int64 | Type::UInt32 | Type::UInt64
+        )
+    }
+
+    /// 符号付きかどうか. 比較や割り算, 右シフト, 拡張の命令が変わる
+    pub fn is_signed(&self) -> bool {
+        matches!(self, Type::Int32 | Type::Int64)
+    }
+
+    /// 整数型で表せる値の範囲
+    pub fn int_range(&self) -> Option<(i128, i128)> {
+        match self {
+            Type::Int32 => Some((i32::MIN as i128, i32::MAX as i128)),
+            Type::Int64 => Some((i64::MIN as i128, i64::MAX as i128)),
+            Type::UInt32 => Some((0, u32::MAX as i128)),
+            Type::UInt64 => Some((0, u64::MAX as i128)),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Type {
@@ -16,9 +54,16 @@ impl fmt::Display for Type {
             Type::Unknown => write!(f, "unknown"),
             Type::Int32 => write!(f, "int32"),
             Type::Int64 => write!(f, "int64"),
+            Type::UInt32 => write!(f, "uint32"),
+            Type::UInt64 => write!(f, "uint64"),
             Type::String => write!(f, "string"),
             Type::Bool => write!(f, "bool"),
             Type::Unit => write!(f, "unit"),
+            Type::Array(elem, len) => write!(f, "[{}; {}]", elem, len),
+            Type::ArrayConstLen(elem, len) => write!(f, "[{}; {}]", elem, len),
+            Type::Pointer(inner) => write!(f, "*{}", inner),
+            Type::Named(name) => write!(f, "{}", name),
+            Type::Error => write!(f, "{{error}}"),
         }
     }
 }
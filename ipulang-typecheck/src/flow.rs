@@ -0,0 +1,198 @@
+use ipulang_parser::{
+    diagnostics::Diagnostic,
+    nodes::{ArmBody, Const, Expr, FunctionDecl, Program, Span, Stmt, Stmts},
+    types::Type,
+};
+
+/// 制御フローを調べる
+/// 値を返す関数の終わりまで到達できたらエラー, 到達できない文は警告にする
+pub fn check_flow(program: &Program) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    for function in program.functions() {
+        check_function(function, &mut diagnostics);
+    }
+    diagnostics
+}
+
+fn check_function(function: &FunctionDecl, diagnostics: &mut Vec<Diagnostic>) {
+    let falls_through = stmts_falls_through(&function.stmts, &function.id, diagnostics);
+    if falls_through && function.ret_typ != Type::Unit {
+        diagnostics.push(
+            Diagnostic::error(
+                "E0108",
+                format!("not all paths return a value in function `{}`", function.id),
+            )
+            .with_primary(
+                function.position,
+                format!("this function returns `{}`", function.ret_typ),
+            )
+            .with_help("add a `return` at the end of the function"),
+        );
+    }
+}
+
+/// 文の並びを最後まで実行し終えて次に進むことがあるかどうか
+fn stmts_falls_through(stmts: &Stmts, fn_id: &str, diagnostics: &mut Vec<Diagnostic>) -> bool {
+    let mut iter = stmts.0.iter();
+    while let Some(stmt) = iter.next() {
+        if !stmt_falls_through(stmt, fn_id, diagnostics) {
+            // 残りは実行されない. 最初の文だけ警告する
+            if let Some(next) = iter.next() {
+                let warning = Diagnostic::warning("W0001", "unreachable statement")
+                    .with_note(format!("in function `{}`", fn_id));
+                let warning = match stmt_position(next) {
+                    Some(pos) => warning.with_primary(pos, "this statement is never executed"),
+                    None => warning,
+                };
+                diagnostics.push(warning);
+            }
+            return false;
+        }
+    }
+    true
+}
+
+fn stmt_falls_through(stmt: &Stmt, fn_id: &str, diagnostics: &mut Vec<Diagnostic>) -> bool {
+    match stmt {
+        Stmt::Return(_) | Stmt::Break(_) | Stmt::Continue(_) => false,
+        Stmt::IfElse(if_else) => {
+            let success = stmts_falls_through(&if_else.success, fn_id, diagnostics);
+            match &if_else.failure {
+                Some(failure) => {
+                    let failure = stmts_falls_through(failure, fn_id, diagnostics);
+                    success || failure
+                }
+                None => true,
+            }
+        }
+        // ループは条件次第で抜けるので, 中身だけ調べる
+        Stmt::For(for_stmt) => {
+            stmts_falls_through(&for_stmt.stmts, fn_id, diagnostics);
+            true
+        }
+        // 条件が定数の真で `break` もなければ, ループを抜けることはない
+        Stmt::While(while_stmt) => {
+            stmts_falls_through(&while_stmt.stmts, fn_id, diagnostics);
+            let infinite = matches!(while_stmt.cond, Expr::Const(Const::BoolConst(true)));
+            !infinite || stmts_breaks(&while_stmt.stmts)
+        }
+        // どれかの腕が最後まで進めば次に進む. 網羅していない match は型検査でエラーにする
+        Stmt::Match(m) => m.arms.iter().fold(false, |falls_through, arm| {
+            let arm_falls_through = match &arm.body {
+                ArmBody::Block(stmts) => stmts_falls_through(stmts, fn_id, diagnostics),
+                ArmBody::Expr(_) => true,
+            };
+            falls_through || arm_falls_through
+        }),
+        Stmt::Expr(_) | Stmt::VariableDecl(_) | Stmt::Assign(_) => true,
+    }
+}
+
+/// このループを抜ける `break` があるかどうか. 内側のループの `break` は数えない
+fn stmts_breaks(stmts: &Stmts) -> bool {
+    stmts.0.iter().any(|stmt| match stmt {
+        Stmt::Break(_) => true,
+        Stmt::IfElse(if_else) => {
+            stmts_breaks(&if_else.success) || if_else.failure.as_ref().map_or(false, stmts_breaks)
+        }
+        Stmt::Match(m) => m.arms.iter().any(|arm| match &arm.body {
+            ArmBody::Block(stmts) => stmts_breaks(stmts),
+            ArmBody::Expr(_) => false,
+        }),
+        _ => false,
+    })
+}
+
+fn stmt_position<'a>(stmt: &Stmt<'a>) -> Option<Span<'a>> {
+    match stmt {
+        Stmt::Expr(expr) | Stmt::Return(expr) => expr.position(),
+        Stmt::VariableDecl(var_decl) => Some(var_decl.position),
+        Stmt::Assign(assign) => Some(assign.position),
+        Stmt::IfElse(if_else) => Some(if_else.position),
+        Stmt::For(for_stmt) => Some(for_stmt.position),
+        Stmt::While(while_stmt) => Some(while_stmt.position),
+        Stmt::Match(m) => Some(m.position),
+        Stmt::Break(position) | Stmt::Continue(position) => Some(*position),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipulang_parser::ast::program_parser;
+
+    fn check(code: &str) -> Vec<Diagnostic> {
+        let program = program_parser(Span::new(code)).unwrap();
+        check_flow(&program)
+    }
+
+    #[test]
+    fn test_missing_return() {
+        let codes = vec![
+            "fn main(): i32 { var a: i32 = 1; }",
+            "fn main(): i32 { if (1_bool) { return 1; } }",
+            "fn main(): i32 { if (1_bool) { return 1; } else { } }",
+            "fn main(): i32 { while (1_bool) { if (a) { break; } } }",
+            "fn main(): i32 { while (a) { return 1; } }",
+            "fn main(): i32 { match (e) { E::A => { return 1; } _ => 2 } }",
+        ];
+        for code in codes {
+            let diagnostics = check(code);
+            assert_eq!(diagnostics.len(), 1, "{}", code);
+            assert!(
+                diagnostics[0]
+                    .to_string()
+                    .contains("not all paths return a value in function `main`"),
+                "{}",
+                code
+            );
+        }
+
+        let codes = vec![
+            "fn main(): unit { }",
+            "fn main(): i32 { return 1; }",
+            "fn main(): i32 { if (1_bool) { return 1; } else { return 2; } }",
+            "fn main(): i32 { if (1_bool) { return 1; } else if (0_bool) { return 2; } else { return 3; } }",
+            "fn main(): i32 { match (e) { E::A => { return 1; } _ => { return 2; } } }",
+            "fn main(): i32 { while (1_bool) { return 1; } }",
+            "fn main(): i32 { while (1_bool) { while (1_bool) { break; } } }",
+        ];
+        for code in codes {
+            assert_eq!(check(code), vec![], "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let code = r#"
+fn main(): i32 {
+    while (1_bool) {
+        break;
+        a = 1;
+    }
+    return 0;
+    putchar(1);
+    return 1;
+}
+
+fn f(): unit {
+    while (1_bool) {
+        continue;
+        a = 1;
+    }
+    putchar(1);
+}
+"#;
+        let warnings = check(code);
+        let warnings: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "5:9: warning[W0001]: unreachable statement",
+                "8:5: warning[W0001]: unreachable statement",
+                "15:9: warning[W0001]: unreachable statement",
+                "17:5: warning[W0001]: unreachable statement",
+            ]
+        );
+    }
+}
@@ -1,304 +1,2526 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use anyhow::{anyhow, bail, ensure, Result};
+use crate::flow::check_flow;
 use ipulang_parser::{
+    diagnostics::Diagnostic,
     nodes::{
-        Assign, BinOp, Call, Const, Expr, For, FunctionDecl, IfElse, Op, Program, Stmt, Stmts,
-        Variable, VariableDecl,
+        ArmBody, ArrayLiteral, Assign, BinOp, Call, Cast, Const, EnumDecl, Expr, ExternFn, Field,
+        For, FunctionDecl, GlobalDecl, IfElse, Index, IntLiteral, Item, Match, Op, Pattern,
+        Program, Span, Stmt, Stmts, StructDecl, StructLiteral, UnOp, UnaryOp, Variable,
+        VariableDecl, VariantLiteral, VariantPattern, While,
     },
+    prelude::prelude,
     types::Type,
 };
 
+/// 関数の引数と戻り値の型
+#[derive(Debug, Clone)]
+struct Signature {
+    params: Vec<Type>,
+    ret: Type,
+    /// 引数の最後が `...` かどうか. `params` より後ろの引数は何でもよい
+    variadic: bool,
+}
+
+impl Signature {
+    fn of_function(function: &FunctionDecl) -> Self {
+        Self {
+            params: function.args.iter().map(|arg| arg.ty.clone()).collect(),
+            ret: function.ret_typ.clone(),
+            variadic: false,
+        }
+    }
+
+    fn of_extern(decl: &ExternFn) -> Self {
+        Self {
+            params: decl.args.iter().map(|arg| arg.ty.clone()).collect(),
+            ret: decl.ret_typ.clone(),
+            variadic: decl.variadic,
+        }
+    }
+}
+
 struct Env {
-    /// 変数の情報
-    variables: HashMap<String, HashMap<String, Type>>,
+    /// 変数の情報. ブロックごとのスコープで, 内側のスコープほど後ろ
+    scopes: Vec<HashMap<String, Type>>,
     /// 現在のfunction
     function_id: Option<String>,
-    functions: HashMap<String, (Vec<Type>, Type)>,
+    functions: HashMap<String, Signature>,
+    /// 構造体名 -> フィールドの名前と型. 宣言した順に並ぶ
+    structs: HashMap<String, Vec<(String, Type)>>,
+    /// enum 名 -> バリアントの名前と持っている値の型. 宣言した順に並ぶ
+    enums: HashMap<String, Vec<(String, Vec<Type>)>>,
+    /// 定数名 -> 型とコンパイル時に計算した値. 配列の長さにも使える
+    consts: HashMap<String, (Type, i128)>,
+    /// 今いるループの深さ. break, continue はループの中でしか書けない
+    loop_depth: usize,
+    /// 見つかったエラー. 最初のエラーで止めずに全て集める
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl Env {
     fn new() -> Self {
         let mut functions = HashMap::new();
-        // decrare putchar(i32): i32
-        let putchar_type = (vec![Type::Int32], Type::Int32);
-        functions.insert("putchar".to_owned(), putchar_type);
-
-        // decrate getchar(): i32
-        functions.insert("getchar".to_owned(), (vec![], Type::Int32));
+        for decl in prelude() {
+            functions.insert(decl.id.clone(), Signature::of_extern(&decl));
+        }
 
         Self {
-            variables: HashMap::new(),
+            scopes: vec![],
             function_id: None,
             functions: functions,
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            consts: HashMap::new(),
+            loop_depth: 0,
+            diagnostics: vec![],
         }
     }
 
-    /// 変数の型情報を取得する
+    fn report(&mut self, diag: Diagnostic) {
+        self.diagnostics.push(diag);
+    }
+
+    /// 新しいスコープの中で `f` を実行する
+    fn in_scope<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(HashMap::new());
+        let res = f(self);
+        self.scopes.pop();
+        res
+    }
+
+    /// 変数の型情報を取得する. 内側のスコープから探す
     fn get_var_type(&mut self, var_name: &str) -> Option<Type> {
-        self.function_id
-            .clone()
-            .map(|id| self.variables.get(&id).map(|m| m.get(var_name)).flatten())
-            .flatten()
-            .map(|ty| *ty)
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(var_name))
+            .cloned()
     }
 
-    /// 変数に型情報を設定する
-    fn set_var_type(&mut self, var_name: String, typ: Type) {
-        let map = self
-            .variables
-            .get_mut(&self.function_id.clone().unwrap())
-            .unwrap();
-        map.insert(var_name, typ);
+    /// `name` が定数を指しているか. 同じ名前のローカル変数があればそちらを指す
+    fn is_const(&self, name: &str) -> bool {
+        // 定数は一番外側のスコープにある
+        let scope = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(name));
+        scope == Some(0) && self.consts.contains_key(name)
+    }
+
+    /// 今のスコープに変数を宣言する
+    /// 外側の変数は隠せるが, 同じスコープでの再宣言はエラー
+    fn declare_var(&mut self, var_name: String, typ: Type, position: Span) {
+        let scope = self.scopes.last_mut().unwrap();
+        if scope.contains_key(&var_name) {
+            self.report(
+                Diagnostic::error(
+                    "E0105",
+                    format!("variable `{}` is already declared in this scope", var_name),
+                )
+                .with_primary(position, "redeclared here")
+                .with_help("use a different name, or assign to the existing variable"),
+            );
+            return;
+        }
+        scope.insert(var_name, typ);
     }
 
-    fn get_current_fn_type(&self) -> Option<(Vec<Type>, Type)> {
+    /// エラーの補足に使う今の関数名
+    fn function_note(&self) -> String {
+        format!(
+            "in function `{}`",
+            self.function_id.as_deref().unwrap_or_default()
+        )
+    }
+
+    fn get_current_fn_type(&self) -> Option<Signature> {
         self.function_id
             .clone()
             .map(|a| self.get_fn_type(&a))
             .flatten()
     }
-    fn get_fn_type(&self, fn_name: &String) -> Option<(Vec<Type>, Type)> {
+    fn get_fn_type(&self, fn_name: &String) -> Option<Signature> {
         self.functions.get(fn_name).map(|ty| ty.clone())
     }
 }
 
-pub fn type_check(mut program: Program) -> Result<Program> {
+pub fn type_check(program: Program) -> Result<Program, Vec<Diagnostic>> {
+    type_check_with_warnings(program).map(|(program, _)| program)
+}
+
+/// 型検査と制御フローの検査をして, 警告も返す
+/// エラーがあった時は見つかったエラーを全て返す
+pub fn type_check_with_warnings(
+    mut program: Program,
+) -> Result<(Program, Vec<Diagnostic>), Vec<Diagnostic>> {
     let mut env = Env::new();
-    program.type_check(&mut env)?;
-    Ok(program)
+    program.type_check(&mut env);
+    env.diagnostics.extend(check_flow(&program));
+
+    let (errors, warnings): (Vec<_>, Vec<_>) =
+        env.diagnostics.into_iter().partition(|d| d.is_error());
+    if errors.is_empty() {
+        Ok((program, warnings))
+    } else {
+        Err(errors)
+    }
 }
 
+/// 型が合っているか. 既にエラーになっている型は何とでも合うことにする
+fn compatible(a: &Type, b: &Type) -> bool {
+    a == b || *a == Type::Error || *b == Type::Error
+}
+
+/// 型の決まっていない整数リテラルの型か. `[1, 2]` のような配列も含む
+fn is_untyped(ty: &Type) -> bool {
+    match ty {
+        Type::Unknown => true,
+        Type::Array(elem, _) => is_untyped(elem),
+        _ => false,
+    }
+}
+
+/// 型に出てくる構造体や enum が宣言されているか
+fn check_type(ty: &Type, position: Span, env: &mut Env) -> bool {
+    match ty {
+        Type::Array(inner, _) | Type::Pointer(inner) => check_type(inner, position, env),
+        Type::Named(name) if !env.structs.contains_key(name) && !env.enums.contains_key(name) => {
+            env.report(
+                Diagnostic::error("E0116", format!("cannot find type `{}`", name))
+                    .with_primary(position, "not found in this program"),
+            );
+            false
+        }
+        _ => true,
+    }
+}
+
+/// 配列の長さに書いた定数の名前を値にする. 使えない名前なら報告して `Type::Error` にする
+fn resolve_len(ty: &mut Type, position: Span, env: &mut Env) -> bool {
+    let resolved = match ty {
+        Type::Array(inner, _) | Type::Pointer(inner) => resolve_len(inner, position, env),
+        Type::ArrayConstLen(elem, name) => {
+            let (mut elem, name) = ((**elem).clone(), name.clone());
+            let len = match env.consts.get(&name).cloned() {
+                Some((const_ty, value))
+                    if const_ty.is_integer() && (0..=u32::MAX as i128).contains(&value) =>
+                {
+                    Some(value as u64)
+                }
+                Some((const_ty, value)) => {
+                    env.report(
+                        Diagnostic::error(
+                            "E0124",
+                            format!(
+                                "array length `{}` must be an integer from 0 to {}",
+                                name,
+                                u32::MAX
+                            ),
+                        )
+                        .with_primary(position, "invalid array length")
+                        .with_note(format!("`{}` is `{}` of type `{}`", name, value, const_ty)),
+                    );
+                    None
+                }
+                None => {
+                    env.report(
+                        Diagnostic::error("E0102", format!("cannot find constant `{}`", name))
+                            .with_primary(position, "not found in this program")
+                            .with_help(format!("declare it first: `const {}: i32 = ...;`", name)),
+                    );
+                    None
+                }
+            };
+            match len {
+                Some(len) if resolve_len(&mut elem, position, env) => {
+                    *ty = Type::Array(Box::new(elem), len);
+                    true
+                }
+                _ => false,
+            }
+        }
+        _ => true,
+    };
+    if !resolved {
+        *ty = Type::Error;
+    }
+    resolved
+}
+
+/// 位置が分かれば primary label を付ける
+fn label_at(diag: Diagnostic, position: Option<Span>, message: impl Into<String>) -> Diagnostic {
+    match position {
+        Some(position) => diag.with_primary(position, message),
+        None => diag,
+    }
+}
+
+/// `expected` のはずの式 `expr` が `found` 型だった
+fn mismatched_types(expr: &Expr, expected: Type, found: Type) -> Diagnostic {
+    label_at(
+        Diagnostic::error("E0101", "mismatched types"),
+        expr.position(),
+        format!("expected `{}`, found `{}`", expected, found),
+    )
+}
+
+/// 型の決まっていない整数リテラルを含む式 (`found` が `Type::Unknown`) の型を文脈の `expected` に決める
+/// `var n: i64 = 5;` と `5_i64` を書かずに済むようにする. 文脈が整数型でなければ i32 にする
+fn resolve_int(expr: &mut Expr, expected: Type, found: Type, env: &mut Env) -> Type {
+    if !is_untyped(&found) {
+        return found;
+    }
+    // match は腕ごとに決める
+    if let Expr::Match(m) = expr {
+        for arm in m.arms.iter_mut() {
+            if let ArmBody::Expr(expr) = &mut arm.body {
+                let found = expr.ty();
+                resolve_int(expr, expected.clone(), found, env);
+            }
+        }
+        m.ty = arm_type(m);
+        return m.ty.clone();
+    }
+    // 配列リテラルは要素ごとに決める
+    if let Expr::ArrayLiteral(array) = expr {
+        let elem_expected = match expected {
+            Type::Array(elem, _) => *elem,
+            _ => Type::Unknown,
+        };
+        for elem in array.elems.iter_mut() {
+            let found = elem.ty();
+            resolve_int(elem, elem_expected.clone(), found, env);
+        }
+        let elem_ty = match array.elems.first() {
+            Some(first) => first.ty(),
+            None if elem_expected != Type::Unknown => elem_expected,
+            None => Type::Int32,
+        };
+        array.ty = Type::Array(Box::new(elem_ty), array.elems.len() as u64);
+        return array.ty.clone();
+    }
+    let ty = if expected.is_integer() {
+        expected
+    } else {
+        Type::Int32
+    };
+    match expr {
+        Expr::IntLiteral(lit) => {
+            lit.ty = ty;
+            check_range(lit, env);
+        }
+        Expr::BinOp(bin_op) => {
+            // 型が決まっていない二項演算は算術演算かシフトだけ
+            let left_typ = bin_op.left.ty();
+            resolve_int(&mut bin_op.left, ty.clone(), left_typ, env);
+            if !matches!(bin_op.op, Op::Shl | Op::Shr) {
+                let right_typ = bin_op.right.ty();
+                resolve_int(&mut bin_op.right, ty.clone(), right_typ, env);
+            }
+            bin_op.ty = ty;
+        }
+        Expr::UnaryOp(unary_op) => {
+            let typ = unary_op.expr.ty();
+            resolve_int(&mut unary_op.expr, ty.clone(), typ, env);
+            if unary_op.op == UnOp::Neg && !ty.is_signed() {
+                env.report(
+                    Diagnostic::error(
+                        "E0107",
+                        format!("cannot apply unary operator to type `{}`", ty),
+                    )
+                    .with_primary(unary_op.position, format!("not supported for `{}`", ty)),
+                );
+                unary_op.ty = Type::Error;
+                return Type::Error;
+            }
+            unary_op.ty = ty;
+        }
+        _ => {}
+    }
+    expr.ty()
+}
+
+/// 整数リテラルが型に収まるか
+fn check_range(lit: &IntLiteral, env: &mut Env) {
+    let (min, max) = match lit.ty.int_range() {
+        Some(range) => range,
+        None => return,
+    };
+    if min <= lit.value && lit.value <= max {
+        return;
+    }
+    let diag = Diagnostic::error(
+        "E0111",
+        format!("integer literal out of range for `{}`", lit.ty),
+    )
+    .with_primary(lit.position, format!("this does not fit in `{}`", lit.ty))
+    .with_note(format!("the range of `{}` is {}..={}", lit.ty, min, max));
+    // 収まる型があれば教える
+    let wider = [Type::Int64, Type::UInt64].into_iter().find(|ty| {
+        let (min, max) = ty.int_range().unwrap();
+        min <= lit.value && lit.value <= max
+    });
+    let diag = match wider {
+        Some(ty) => diag.with_help(format!("use a type that can hold it, such as `{}`", ty)),
+        None => diag,
+    };
+    env.report(diag);
+}
+
+/// 式を調べて, 型の決まっていない整数リテラルを `expected` (無ければ i32) にする
+fn check_expr(expr: &mut Expr, expected: Type, env: &mut Env) -> Type {
+    let found = expr.type_check(env);
+    resolve_int(expr, expected, found, env)
+}
+
+/// 型を調べて設定する. エラーは `env` に報告して `Type::Error` を返す
 trait TypeCheck {
-    fn type_check(&mut self, env: &mut Env) -> Result<Type>;
+    fn type_check(&mut self, env: &mut Env) -> Type;
 }
 
 impl TypeCheck for Const {
-    fn type_check(&mut self, env: &mut Env) -> Result<Type> {
-        match self {
-            Const::I32Const(_) => Ok(Type::Int32),
-            Const::I64Const(_) => Ok(Type::Int64),
-            Const::BoolConst(_) => Ok(Type::Bool),
-            // Const::String(_) => Ok(Type::String),
-        }
+    fn type_check(&mut self, _env: &mut Env) -> Type {
+        self.ty()
+    }
+}
+
+impl<'a> TypeCheck for IntLiteral<'a> {
+    /// 型を書いていなければ `Type::Unknown` のまま返し, 外側で決める
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        check_range(self, env);
+        self.ty.clone()
     }
 }
 
 impl<'a> TypeCheck for BinOp<'a> {
-    fn type_check(&mut self, env: &mut Env) -> Result<Type> {
-        let left_typ = self.left.type_check(env)?;
-        let right_typ = self.right.type_check(env)?;
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        let mut left_typ = self.left.type_check(env);
+        let mut right_typ = self.right.type_check(env);
+
+        // シフトは右辺の整数型が左辺と違ってもよい. 左辺の型が決まっていなければ外側で決める
+        if let Op::Shl | Op::Shr = self.op {
+            let right_typ = resolve_int(&mut self.right, Type::Int32, right_typ, env);
+            let is_integer =
+                |ty: &Type| ty.is_integer() || *ty == Type::Error || *ty == Type::Unknown;
+            if !is_integer(&left_typ) || !is_integer(&right_typ) {
+                let typ = if is_integer(&left_typ) {
+                    right_typ
+                } else {
+                    left_typ
+                };
+                env.report(
+                    Diagnostic::error("E0107", format!("cannot shift type `{}`", typ))
+                        .with_primary(self.position, "both operands must be integers"),
+                );
+                self.ty = Type::Error;
+            } else {
+                self.ty = left_typ;
+            }
+            return self.ty.clone();
+        }
+
+        // 両方とも型が決まっていない算術演算は外側で決める. それ以外はもう片方の型に合わせる
+        let arithmetic = matches!(self.op, Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Mod);
+        if left_typ == Type::Unknown && right_typ == Type::Unknown && arithmetic {
+            self.ty = Type::Unknown;
+            return self.ty.clone();
+        }
+        left_typ = resolve_int(&mut self.left, right_typ.clone(), left_typ, env);
+        right_typ = resolve_int(&mut self.right, left_typ.clone(), right_typ, env);
+
+        // 文字列, 配列, ポインタ, 構造体, enum は演算できない
+        let unsupported = [&left_typ, &right_typ].into_iter().find(|ty| {
+            matches!(
+                ty,
+                Type::String | Type::Array(..) | Type::Pointer(_) | Type::Named(_)
+            )
+        });
+        if let Some(typ) = unsupported {
+            env.report(
+                Diagnostic::error(
+                    "E0107",
+                    format!("cannot apply binary operator to type `{}`", typ),
+                )
+                .with_primary(self.position, format!("not supported for `{}`", typ)),
+            );
+            self.ty = Type::Error;
+            return self.ty.clone();
+        }
 
         // 型チェック
-        ensure!(
-            left_typ == right_typ,
-            "op: {:?}, type mismatch!, {:?} != {:?}",
-            self.op,
-            left_typ,
-            right_typ,
-        );
+        if !compatible(&left_typ, &right_typ) {
+            let diag = Diagnostic::error("E0101", "mismatched types")
+                .with_primary(
+                    self.position,
+                    format!("`{}` and `{}` cannot be mixed", left_typ, right_typ),
+                )
+                .with_help("both operands must have the same type");
+            let diag = match self.left.position() {
+                Some(pos) => diag.with_secondary(pos, format!("this is `{}`", left_typ)),
+                None => diag,
+            };
+            let diag = match self.right.position() {
+                Some(pos) => diag.with_secondary(pos, format!("this is `{}`", right_typ)),
+                None => diag,
+            };
+            env.report(diag);
+            self.ty = Type::Error;
+            return self.ty.clone();
+        }
 
         // 型を設定
         self.ty = match self.op {
             Op::Or | Op::And | Op::Eq | Op::Neq | Op::Geq | Op::Leq | Op::Gt | Op::Lt => Type::Bool,
+            Op::Shl | Op::Shr => unreachable!(),
+            // 片方がエラーならもう片方の型にしておく
+            Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Mod if left_typ == Type::Error => right_typ,
             Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Mod => left_typ,
         };
 
-        ensure!(self.ty != Type::Unknown, "type is still unknown",);
-        Ok(self.ty)
+        self.ty.clone()
+    }
+}
+
+/// `&x`. 変数や要素のアドレスだけ取れる
+fn check_ref(unary_op: &mut UnaryOp, env: &mut Env) -> Type {
+    let typ = check_expr(&mut unary_op.expr, Type::Unknown, env);
+    if let Expr::Variable(var) = &unary_op.expr {
+        if env.is_const(&var.id) {
+            env.report(
+                Diagnostic::error(
+                    "E0114",
+                    format!("cannot take the address of constant `{}`", var.id),
+                )
+                .with_primary(unary_op.position, "constants have no address")
+                .with_help("copy it to a variable first"),
+            );
+            unary_op.ty = Type::Error;
+            return unary_op.ty.clone();
+        }
+    }
+    if !unary_op.expr.is_place() {
+        env.report(
+            Diagnostic::error("E0114", "cannot take the address of this expression")
+                .with_primary(
+                    unary_op.position,
+                    "only variables and their elements have an address",
+                )
+                .with_help("store it in a variable first"),
+        );
+        unary_op.ty = Type::Error;
+        return unary_op.ty.clone();
+    }
+    unary_op.ty = match typ {
+        Type::Error => Type::Error,
+        typ => Type::Pointer(Box::new(typ)),
+    };
+    unary_op.ty.clone()
+}
+
+/// `*p`
+fn check_deref(unary_op: &mut UnaryOp, env: &mut Env) -> Type {
+    unary_op.ty = match check_expr(&mut unary_op.expr, Type::Unknown, env) {
+        Type::Pointer(inner) => *inner,
+        Type::Error => Type::Error,
+        typ => {
+            env.report(
+                Diagnostic::error("E0115", format!("cannot dereference type `{}`", typ))
+                    .with_primary(unary_op.position, "only pointers can be dereferenced"),
+            );
+            Type::Error
+        }
+    };
+    unary_op.ty.clone()
+}
+
+impl<'a> TypeCheck for UnaryOp<'a> {
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        match self.op {
+            UnOp::Ref => return check_ref(self, env),
+            UnOp::Deref => return check_deref(self, env),
+            _ => {}
+        }
+        let typ = self.expr.type_check(env);
+        if typ == Type::Unknown && self.op != UnOp::Not {
+            self.ty = Type::Unknown;
+            return self.ty.clone();
+        }
+        let typ = resolve_int(&mut self.expr, Type::Int32, typ, env);
+
+        // -: 符号付き整数, !: bool, ~: 整数
+        let supported = match self.op {
+            UnOp::Neg => typ.is_signed() || typ == Type::Error,
+            UnOp::BitNot => typ.is_integer() || typ == Type::Error,
+            UnOp::Not => compatible(&typ, &Type::Bool),
+            UnOp::Ref | UnOp::Deref => unreachable!(),
+        };
+        if !supported {
+            env.report(
+                Diagnostic::error(
+                    "E0107",
+                    format!("cannot apply unary operator to type `{}`", typ),
+                )
+                .with_primary(self.position, format!("not supported for `{}`", typ)),
+            );
+            self.ty = Type::Error;
+            return self.ty.clone();
+        }
+
+        self.ty = typ;
+        self.ty.clone()
+    }
+}
+
+impl<'a> TypeCheck for Cast<'a> {
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        let from = check_expr(&mut self.expr, Type::Int32, env);
+        resolve_len(&mut self.ty, self.position, env);
+
+        // 整数同士, bool から整数, bool から bool へのキャストだけできる
+        // codegen は整数として変換するので, 同じ型でもそれ以外の型は使えない
+        let legal = from == Type::Error
+            || self.ty == Type::Error
+            || (from == Type::Bool && self.ty == Type::Bool)
+            || (self.ty.is_integer() && (from.is_integer() || from == Type::Bool));
+        if !legal {
+            let diag =
+                Diagnostic::error("E0109", format!("cannot cast `{}` as `{}`", from, self.ty))
+                    .with_primary(self.position, "invalid cast");
+            let diag = if from.is_integer() && self.ty == Type::Bool {
+                diag.with_help("compare with zero instead: `x != 0`")
+            } else {
+                diag
+            };
+            env.report(diag);
+        }
+        // 失敗しても変換先の型として扱う
+        self.ty.clone()
     }
 }
 
 impl<'a> TypeCheck for Variable<'a> {
-    fn type_check(&mut self, env: &mut Env) -> Result<Type> {
+    fn type_check(&mut self, env: &mut Env) -> Type {
         if let Some(typ) = env.get_var_type(&self.id) {
-            Ok(typ)
+            self.ty = typ.clone();
+            typ
         } else {
-            Err(anyhow!("variable not found: {}", &self.id))
+            env.report(
+                Diagnostic::error("E0102", format!("cannot find variable `{}`", self.id))
+                    .with_primary(self.position, "not found in this scope"),
+            );
+            Type::Error
         }
     }
 }
 
 impl<'a> TypeCheck for Call<'a> {
-    fn type_check(&mut self, env: &mut Env) -> Result<Type> {
+    fn type_check(&mut self, env: &mut Env) -> Type {
         let func_name = self.id.clone();
-        if let Some(func_type) = env.functions.get(&func_name).map(|a| a.clone()) {
-            // 引数の数をチェック
-            ensure!(
-                func_type.0.len() == self.args.len(),
-                "function {} takes {} arguments, but {} given",
-                func_name,
-                func_type.0.len(),
-                self.args.len(),
+        let func_type = match env.functions.get(&func_name).map(|a| a.clone()) {
+            Some(func_type) => func_type,
+            None => {
+                // 引数の中のエラーも見つけておく
+                for arg in self.args.iter_mut() {
+                    check_expr(arg, Type::Unknown, env);
+                }
+                // const と static の初期値は関数を登録する前に調べるので, 見つからなくても当然
+                let diag = if env.function_id.is_none() {
+                    not_const(Some(self.position))
+                } else {
+                    Diagnostic::error("E0103", format!("cannot find function `{}`", func_name))
+                        .with_primary(self.position, "not found")
+                };
+                env.report(diag);
+                return Type::Error;
+            }
+        };
+
+        // 引数の数をチェック
+        if func_type.variadic && self.args.len() < func_type.params.len() {
+            env.report(
+                Diagnostic::error(
+                    "E0104",
+                    format!(
+                        "function `{}` takes at least {} arguments but {} were given",
+                        func_name,
+                        func_type.params.len(),
+                        self.args.len(),
+                    ),
+                )
+                .with_primary(
+                    self.position,
+                    format!("expected at least {} arguments", func_type.params.len()),
+                ),
             );
-            // 引数の型をチェック
-            for (arg, param_type) in self.args.iter_mut().zip(func_type.0.iter()) {
-                let arg_typ = arg.type_check(env)?;
-                ensure!(
-                    arg_typ == *param_type,
-                    format!("type mismatch!, {:?} != {:?}", arg_typ, param_type,),
+        } else if !func_type.variadic && func_type.params.len() != self.args.len() {
+            env.report(
+                Diagnostic::error(
+                    "E0104",
+                    format!(
+                        "function `{}` takes {} arguments but {} were given",
+                        func_name,
+                        func_type.params.len(),
+                        self.args.len(),
+                    ),
+                )
+                .with_primary(
+                    self.position,
+                    format!("expected {} arguments", func_type.params.len()),
+                ),
+            );
+        }
+        // 引数の型をチェック
+        for (i, arg) in self.args.iter_mut().enumerate() {
+            let param_type = func_type.params.get(i).cloned().unwrap_or(Type::Unknown);
+            let arg_typ = check_expr(arg, param_type, env);
+            match func_type.params.get(i) {
+                Some(param_type) => {
+                    if !compatible(&arg_typ, param_type) {
+                        env.report(
+                            mismatched_types(arg, param_type.clone(), arg_typ).with_secondary(
+                                self.position,
+                                "arguments to this function are incorrect",
+                            ),
+                        );
+                    }
+                }
+                // 可変長引数はそのまま C に渡すので, 配列や構造体などを値で渡すことはできない
+                None => {
+                    let passable = arg_typ.is_integer()
+                        || matches!(
+                            arg_typ,
+                            Type::Bool | Type::String | Type::Pointer(_) | Type::Error
+                        );
+                    if !passable {
+                        env.report(
+                            label_at(
+                                Diagnostic::error("E0101", "mismatched types"),
+                                arg.position(),
+                                format!("`{}` cannot be passed as a variadic argument", arg_typ),
+                            )
+                            .with_secondary(
+                                self.position,
+                                "arguments to this function are incorrect",
+                            )
+                            .with_help("pass an integer, `bool`, `string` or pointer"),
+                        );
+                    }
+                }
+            }
+        }
+        // 引数が間違っていても戻り値の型は分かる
+        self.ty = func_type.ret;
+        self.ty.clone()
+    }
+}
+
+impl<'a> TypeCheck for ArrayLiteral<'a> {
+    /// 要素が全て型の決まっていない整数リテラルなら, 要素の型は外側で決める
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        let types: Vec<Type> = self
+            .elems
+            .iter_mut()
+            .map(|elem| elem.type_check(env))
+            .collect();
+        let len = self.elems.len() as u64;
+        let elem_ty = match types.iter().find(|ty| !is_untyped(ty)) {
+            Some(ty) => ty.clone(),
+            None => {
+                self.ty = Type::Array(Box::new(Type::Unknown), len);
+                return self.ty.clone();
+            }
+        };
+        for (elem, found) in self.elems.iter_mut().zip(types) {
+            let found = resolve_int(elem, elem_ty.clone(), found, env);
+            if !compatible(&elem_ty, &found) {
+                env.report(
+                    mismatched_types(elem, elem_ty.clone(), found)
+                        .with_secondary(self.position, "array elements must have the same type"),
                 );
             }
-            Ok(func_type.1)
-        } else {
-            bail!("function not found: {}", func_name);
         }
+        self.ty = Type::Array(Box::new(elem_ty), len);
+        self.ty.clone()
     }
 }
 
+impl<'a> TypeCheck for Index<'a> {
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        let array_typ = check_expr(&mut self.array, Type::Unknown, env);
+        let index_typ = check_expr(&mut self.index, Type::Int32, env);
+        if !index_typ.is_integer() && index_typ != Type::Error {
+            env.report(label_at(
+                Diagnostic::error("E0101", "mismatched types"),
+                self.index.position(),
+                format!("expected an integer, found `{}`", index_typ),
+            ));
+        }
+        self.ty = match array_typ {
+            Type::Array(elem, len) => {
+                // 添字が定数なら範囲外をここで見つける
+                if let Expr::IntLiteral(lit) = &self.index {
+                    if lit.value < 0 || lit.value >= len as i128 {
+                        env.report(
+                            Diagnostic::error("E0112", "index out of bounds").with_primary(
+                                lit.position,
+                                format!("the length is {} but the index is {}", len, lit.value),
+                            ),
+                        );
+                    }
+                }
+                *elem
+            }
+            Type::Error => Type::Error,
+            typ => {
+                env.report(
+                    Diagnostic::error(
+                        "E0113",
+                        format!("cannot index into a value of type `{}`", typ),
+                    )
+                    .with_primary(self.position, "only arrays can be indexed"),
+                );
+                Type::Error
+            }
+        };
+        self.ty.clone()
+    }
+}
+
+impl<'a> TypeCheck for StructLiteral<'a> {
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        let fields = match env.structs.get(&self.id) {
+            Some(fields) => fields.clone(),
+            None => {
+                for field in self.fields.iter_mut() {
+                    check_expr(&mut field.value, Type::Unknown, env);
+                }
+                env.report(
+                    Diagnostic::error("E0116", format!("cannot find struct `{}`", self.id))
+                        .with_primary(self.position, "not found in this program"),
+                );
+                self.ty = Type::Error;
+                return self.ty.clone();
+            }
+        };
+
+        let mut initialized: Vec<String> = vec![];
+        for field in self.fields.iter_mut() {
+            match fields.iter().find(|(id, _)| *id == field.id) {
+                Some((_, expected)) => {
+                    let found = check_expr(&mut field.value, expected.clone(), env);
+                    if !compatible(expected, &found) {
+                        env.report(mismatched_types(&field.value, expected.clone(), found));
+                    }
+                }
+                None => {
+                    check_expr(&mut field.value, Type::Unknown, env);
+                    env.report(
+                        Diagnostic::error(
+                            "E0117",
+                            format!("struct `{}` has no field named `{}`", self.id, field.id),
+                        )
+                        .with_primary(field.position, "unknown field"),
+                    );
+                    continue;
+                }
+            }
+            if initialized.contains(&field.id) {
+                env.report(
+                    Diagnostic::error(
+                        "E0126",
+                        format!("field `{}` specified more than once", field.id),
+                    )
+                    .with_primary(field.position, "used more than once"),
+                );
+            } else {
+                initialized.push(field.id.clone());
+            }
+        }
+
+        let missing: Vec<String> = fields
+            .iter()
+            .filter(|(id, _)| !initialized.contains(id))
+            .map(|(id, _)| format!("`{}`", id))
+            .collect();
+        if !missing.is_empty() {
+            env.report(
+                Diagnostic::error(
+                    "E0118",
+                    format!(
+                        "missing fields {} in initializer of `{}`",
+                        missing.join(", "),
+                        self.id
+                    ),
+                )
+                .with_primary(self.position, "all fields must be initialized"),
+            );
+        }
+
+        self.ty = Type::Named(self.id.clone());
+        self.ty.clone()
+    }
+}
+
+impl<'a> TypeCheck for Field<'a> {
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        let typ = check_expr(&mut self.expr, Type::Unknown, env);
+        let fields = match &typ {
+            Type::Named(name) => env.structs.get(name).cloned(),
+            _ => None,
+        };
+        self.ty = match (&typ, fields) {
+            (Type::Error, _) => Type::Error,
+            // 構造体が見つからないことは宣言の所で報告している
+            (Type::Named(name), None) if !env.enums.contains_key(name) => Type::Error,
+            (Type::Named(_), Some(fields)) => match fields.iter().find(|(id, _)| *id == self.id) {
+                Some((_, ty)) => ty.clone(),
+                None => {
+                    let names: Vec<String> =
+                        fields.iter().map(|(id, _)| format!("`{}`", id)).collect();
+                    env.report(
+                        Diagnostic::error(
+                            "E0117",
+                            format!("no field `{}` on type `{}`", self.id, typ),
+                        )
+                        .with_primary(self.position, "unknown field")
+                        .with_note(format!("available fields are {}", names.join(", "))),
+                    );
+                    Type::Error
+                }
+            },
+            _ => {
+                let diag =
+                    Diagnostic::error("E0117", format!("no field `{}` on type `{}`", self.id, typ))
+                        .with_primary(self.position, "only structs have fields");
+                let diag = match &typ {
+                    Type::Pointer(inner) if matches!(**inner, Type::Named(_)) => {
+                        diag.with_help(format!("dereference it first: `(*p).{}`", self.id))
+                    }
+                    _ => diag,
+                };
+                env.report(diag);
+                Type::Error
+            }
+        };
+        self.ty.clone()
+    }
+}
+
+/// enum `enum_id` のバリアント `variant` が持つ値の型. 見つからなければ報告して `None`
+fn find_variant(enum_id: &str, variant: &str, position: Span, env: &mut Env) -> Option<Vec<Type>> {
+    let variants = match env.enums.get(enum_id) {
+        Some(variants) => variants.clone(),
+        None => {
+            env.report(
+                Diagnostic::error("E0116", format!("cannot find enum `{}`", enum_id))
+                    .with_primary(position, "not found in this program"),
+            );
+            return None;
+        }
+    };
+    match variants.iter().find(|(id, _)| id == variant) {
+        Some((_, fields)) => Some(fields.clone()),
+        None => {
+            let names: Vec<String> = variants.iter().map(|(id, _)| format!("`{}`", id)).collect();
+            env.report(
+                Diagnostic::error(
+                    "E0121",
+                    format!("no variant named `{}` in enum `{}`", variant, enum_id),
+                )
+                .with_primary(position, "variant not found")
+                .with_note(format!("available variants are {}", names.join(", "))),
+            );
+            None
+        }
+    }
+}
+
+impl<'a> TypeCheck for VariantLiteral<'a> {
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        let fields = match find_variant(&self.enum_id, &self.variant, self.position, env) {
+            Some(fields) => fields,
+            None => {
+                for arg in self.args.iter_mut() {
+                    check_expr(arg, Type::Unknown, env);
+                }
+                self.ty = Type::Error;
+                return self.ty.clone();
+            }
+        };
+
+        if fields.len() != self.args.len() {
+            env.report(
+                Diagnostic::error(
+                    "E0104",
+                    format!(
+                        "variant `{}::{}` has {} fields but {} were given",
+                        self.enum_id,
+                        self.variant,
+                        fields.len(),
+                        self.args.len(),
+                    ),
+                )
+                .with_primary(self.position, format!("expected {} fields", fields.len())),
+            );
+        }
+        for (i, arg) in self.args.iter_mut().enumerate() {
+            let expected = fields.get(i).cloned().unwrap_or(Type::Unknown);
+            let found = check_expr(arg, expected.clone(), env);
+            if i < fields.len() && !compatible(&expected, &found) {
+                env.report(mismatched_types(arg, expected, found));
+            }
+        }
+        // 値が間違っていても enum の型は分かる
+        self.ty = Type::Named(self.enum_id.clone());
+        self.ty.clone()
+    }
+}
+
+/// パターンを調べて, フィールドを入れる変数を今のスコープに宣言する
+/// `enum_id` は調べる値の enum. 当てはまるバリアントが分かれば true
+fn check_pattern(pattern: &mut VariantPattern, enum_id: Option<&str>, env: &mut Env) -> bool {
+    let fields = match enum_id {
+        None => None,
+        Some(enum_id) if pattern.enum_id != enum_id => {
+            env.report(Diagnostic::error("E0101", "mismatched types").with_primary(
+                pattern.position,
+                format!("expected `{}`, found `{}`", enum_id, pattern.enum_id),
+            ));
+            None
+        }
+        Some(_) => find_variant(&pattern.enum_id, &pattern.variant, pattern.position, env),
+    };
+    if let Some(fields) = &fields {
+        if fields.len() != pattern.bindings.len() {
+            env.report(
+                Diagnostic::error(
+                    "E0104",
+                    format!(
+                        "variant `{}::{}` has {} fields but the pattern has {}",
+                        pattern.enum_id,
+                        pattern.variant,
+                        fields.len(),
+                        pattern.bindings.len(),
+                    ),
+                )
+                .with_primary(
+                    pattern.position,
+                    format!("expected {} fields", fields.len()),
+                )
+                .with_help("use `_` for fields you do not need"),
+            );
+        }
+    }
+    // 型の分からない変数もエラーとして宣言しておく
+    for (i, binding) in pattern.bindings.iter_mut().enumerate() {
+        binding.ty = fields
+            .as_ref()
+            .and_then(|fields| fields.get(i).cloned())
+            .unwrap_or(Type::Error);
+        if binding.id != "_" {
+            env.declare_var(binding.id.clone(), binding.ty.clone(), binding.position);
+        }
+    }
+    fields.is_some()
+}
+
+/// 式の match の型. 最初に型の決まった腕に合わせる
+fn arm_type(m: &Match) -> Type {
+    let types: Vec<Type> = m
+        .arms
+        .iter()
+        .map(|arm| match &arm.body {
+            ArmBody::Expr(expr) => expr.ty(),
+            ArmBody::Block(_) => Type::Unit,
+        })
+        .collect();
+    types
+        .iter()
+        .find(|ty| !is_untyped(ty))
+        .or_else(|| types.first())
+        .cloned()
+        .unwrap_or(Type::Unit)
+}
+
+/// `match` を調べる. バリアントを全て扱っていなければエラー
+/// 式の match (`is_expr`) では腕の型をそろえて match の型にする
+fn check_match(m: &mut Match, is_expr: bool, env: &mut Env) -> Type {
+    let typ = check_expr(&mut m.expr, Type::Unknown, env);
+    let enum_id = match &typ {
+        Type::Named(name) if env.enums.contains_key(name) => Some(name.clone()),
+        Type::Error => None,
+        _ => {
+            env.report(label_at(
+                Diagnostic::error("E0122", format!("cannot match on type `{}`", typ)),
+                m.expr.position(),
+                "expected an enum",
+            ));
+            None
+        }
+    };
+
+    let mut covered: Vec<String> = vec![];
+    let mut wildcard = false;
+    for arm in m.arms.iter_mut() {
+        let reachable = match &arm.pattern {
+            Pattern::Wildcard(_) => !wildcard,
+            Pattern::Variant(pattern) => !wildcard && !covered.contains(&pattern.variant),
+        };
+        if !reachable {
+            env.report(
+                Diagnostic::warning("W0002", "unreachable pattern")
+                    .with_primary(arm.pattern.position(), "no value reaches this arm")
+                    .with_note(env.function_note()),
+            );
+        }
+        // フィールドを入れる変数は腕の中だけで見える
+        env.in_scope(|env| {
+            match &mut arm.pattern {
+                Pattern::Wildcard(_) => wildcard = true,
+                Pattern::Variant(pattern) => {
+                    if check_pattern(pattern, enum_id.as_deref(), env) {
+                        covered.push(pattern.variant.clone());
+                    }
+                }
+            }
+            match &mut arm.body {
+                ArmBody::Block(stmts) => {
+                    stmts.type_check(env);
+                }
+                ArmBody::Expr(expr) if is_expr => {
+                    expr.type_check(env);
+                }
+                ArmBody::Expr(expr) => {
+                    check_expr(expr, Type::Unknown, env);
+                }
+            }
+        });
+    }
+
+    if let (Some(enum_id), false) = (&enum_id, wildcard) {
+        let missing: Vec<String> = env.enums[enum_id]
+            .iter()
+            .filter(|(id, _)| !covered.contains(id))
+            .map(|(id, _)| format!("`{}::{}`", enum_id, id))
+            .collect();
+        if !missing.is_empty() {
+            env.report(
+                Diagnostic::error(
+                    "E0120",
+                    format!(
+                        "non-exhaustive patterns: {} not covered",
+                        missing.join(", ")
+                    ),
+                )
+                .with_primary(m.position, "not all variants are handled")
+                .with_help("add arms for the missing variants, or a wildcard arm `_ => ...`"),
+            );
+        }
+    }
+
+    if !is_expr {
+        m.ty = Type::Unit;
+        return m.ty.clone();
+    }
+    // 型の決まっていない腕は他の腕の型に合わせる. 全て決まっていなければ外側で決める
+    m.ty = arm_type(m);
+    let mut reported = false;
+    for arm in m.arms.iter_mut() {
+        if let ArmBody::Expr(expr) = &mut arm.body {
+            let found = expr.ty();
+            let found = if is_untyped(&m.ty) {
+                found
+            } else {
+                resolve_int(expr, m.ty.clone(), found, env)
+            };
+            if !compatible(&m.ty, &found) && !reported {
+                env.report(
+                    mismatched_types(expr, m.ty.clone(), found)
+                        .with_secondary(m.position, "`match` arms must have the same type"),
+                );
+                reported = true;
+            }
+        }
+    }
+    m.ty.clone()
+}
+
 impl<'a> TypeCheck for Expr<'a> {
-    fn type_check(&mut self, env: &mut Env) -> Result<Type> {
+    fn type_check(&mut self, env: &mut Env) -> Type {
         match self {
             Expr::Const(c) => c.type_check(env),
+            Expr::IntLiteral(lit) => lit.type_check(env),
             Expr::BinOp(bin_op) => bin_op.type_check(env),
+            Expr::UnaryOp(unary_op) => unary_op.type_check(env),
+            Expr::Cast(cast) => cast.type_check(env),
             Expr::Variable(var) => var.type_check(env),
             Expr::Call(call) => call.type_check(env),
+            Expr::ArrayLiteral(array) => array.type_check(env),
+            Expr::Index(index) => index.type_check(env),
+            Expr::StructLiteral(literal) => literal.type_check(env),
+            Expr::Field(field) => field.type_check(env),
+            Expr::VariantLiteral(literal) => literal.type_check(env),
+            Expr::Match(m) => check_match(m, true, env),
         }
     }
 }
 
+/// 条件式が bool かどうか
+fn check_cond(cond: &mut Expr, position: Span, keyword: &str, env: &mut Env) {
+    let cond_typ = check_expr(cond, Type::Bool, env);
+    if !compatible(&cond_typ, &Type::Bool) {
+        env.report(mismatched_types(cond, Type::Bool, cond_typ).with_secondary(
+            position,
+            format!("condition of this `{}` must be `bool`", keyword),
+        ));
+    }
+}
+
 impl<'a> TypeCheck for IfElse<'a> {
-    fn type_check(&mut self, env: &mut Env) -> Result<Type> {
-        let cond_typ = self.cond.type_check(env)?;
-        self.success.type_check(env)?;
-        self.failure.as_mut().map(|ref mut f| f.type_check(env));
-
-        ensure!(
-            cond_typ == Type::Bool,
-            "{:?} type mismatch!, {:?} != {:?}",
-            self.cond,
-            cond_typ,
-            Type::Bool,
-        );
-        Ok(Type::Unit)
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        check_cond(&mut self.cond, self.position, "if", env);
+        self.success.type_check(env);
+        if let Some(failure) = self.failure.as_mut() {
+            failure.type_check(env);
+        }
+        Type::Unit
     }
 }
 
 impl<'a> TypeCheck for Assign<'a> {
-    fn type_check(&mut self, env: &mut Env) -> Result<Type> {
-        // TODO: 型推論
-        if let Some(var_typ) = env.get_var_type(&self.left) {
-            let right_typ = self.right.type_check(env)?;
-
-            ensure!(
-                var_typ == right_typ,
-                "type mismatch!, {:?} != {:?}",
-                var_typ,
-                right_typ,
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        // 宣言していない変数への代入は宣言の仕方を教える
+        if let Expr::Variable(var) = &self.left {
+            if env.get_var_type(&var.id).is_none() {
+                check_expr(&mut self.right, Type::Unknown, env);
+                env.report(
+                    Diagnostic::error("E0102", format!("cannot find variable `{}`", var.id))
+                        .with_primary(self.position, "not found in this scope")
+                        .with_help(format!("declare it first: `var {}: <type>;`", var.id)),
+                );
+                return Type::Unit;
+            }
+            if env.is_const(&var.id) {
+                check_expr(&mut self.right, Type::Unknown, env);
+                env.report(
+                    Diagnostic::error("E0123", format!("cannot assign to constant `{}`", var.id))
+                        .with_primary(self.position, "constants cannot be changed")
+                        .with_help(format!(
+                            "use `static mut {}` for a global variable that can be changed",
+                            var.id
+                        )),
+                );
+                return Type::Unit;
+            }
+        }
+        let left_typ = self.left.type_check(env);
+        let right_typ = check_expr(&mut self.right, left_typ.clone(), env);
+        if !compatible(&left_typ, &right_typ) {
+            let note = match &self.left {
+                Expr::Variable(var) => format!("`{}` has type `{}`", var.id, left_typ),
+                _ => format!("this place has type `{}`", left_typ),
+            };
+            env.report(
+                mismatched_types(&self.right, left_typ, right_typ)
+                    .with_secondary(self.position, note),
             );
-        } else {
-            bail!("var {} is not found", &self.left);
         }
-        Ok(Type::Unit)
+        Type::Unit
     }
 }
 
 impl<'a> TypeCheck for For<'a> {
-    fn type_check(&mut self, env: &mut Env) -> Result<Type> {
-        self.var_decl.type_check(env)?;
-        self.cond.type_check(env)?;
-        self.assign.type_check(env)?;
-        self.stmts.type_check(env)?;
-        Ok(Type::Unit)
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        // 初期化した変数は for の中だけで見える
+        env.in_scope(|env| {
+            self.var_decl.type_check(env);
+            check_cond(&mut self.cond, self.position, "for", env);
+            self.assign.type_check(env);
+            env.loop_depth += 1;
+            self.stmts.type_check(env);
+            env.loop_depth -= 1;
+        });
+        Type::Unit
+    }
+}
+
+impl<'a> TypeCheck for While<'a> {
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        check_cond(&mut self.cond, self.position, "while", env);
+        env.loop_depth += 1;
+        self.stmts.type_check(env);
+        env.loop_depth -= 1;
+        Type::Unit
     }
 }
 
 impl<'a> TypeCheck for VariableDecl<'a> {
-    fn type_check(&mut self, env: &mut Env) -> Result<Type> {
-        if let Some(init) = self.init.as_mut() {
-            let init_ty = init.type_check(env)?;
-            ensure!(
-                self.ty == init_ty,
-                "var decl type is not equal. {} != {}",
-                self.ty,
-                init_ty
-            );
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        if !resolve_len(&mut self.ty, self.position, env)
+            || !check_type(&self.ty, self.position, env)
+        {
+            self.ty = Type::Error;
+        }
+        match self.init.as_mut() {
+            // 型を省略したら初期化式の型にする. codegen のために AST に書き戻しておく
+            Some(init) if self.ty == Type::Unknown => {
+                self.ty = check_expr(init, Type::Unknown, env);
+            }
+            Some(init) => {
+                let init_ty = check_expr(init, self.ty.clone(), env);
+                if !compatible(&self.ty, &init_ty) {
+                    env.report(
+                        mismatched_types(init, self.ty.clone(), init_ty).with_secondary(
+                            self.position,
+                            format!("`{}` is declared as `{}`", self.id, self.ty),
+                        ),
+                    );
+                }
+            }
+            None if self.ty == Type::Unknown => {
+                env.report(
+                    Diagnostic::error(
+                        "E0110",
+                        format!("type annotations needed for `{}`", self.id),
+                    )
+                    .with_primary(self.position, "cannot infer the type of this variable")
+                    .with_help(format!(
+                        "give it a type or an initial value: `var {}: i32;`",
+                        self.id
+                    )),
+                );
+                self.ty = Type::Error;
+            }
+            None => {}
+        }
+        // unit は値として持てないので, 変数にはできない
+        if self.ty == Type::Unit {
+            let diag =
+                Diagnostic::error("E0125", format!("variable `{}` has type `unit`", self.id))
+                    .with_primary(self.position, "a variable cannot hold `unit`");
+            env.report(match self.init {
+                Some(_) => diag.with_help("call it as a statement instead of assigning the result"),
+                None => diag,
+            });
+            self.ty = Type::Error;
         }
-        env.set_var_type(self.id.clone(), self.ty.clone());
-        Ok(Type::Unit)
+        // 初期化式が間違っていても宣言した型で使えるようにしておく
+        env.declare_var(self.id.clone(), self.ty.clone(), self.position);
+        Type::Unit
     }
 }
 
 impl<'a> TypeCheck for Stmt<'a> {
-    fn type_check(&mut self, env: &mut Env) -> Result<Type> {
+    fn type_check(&mut self, env: &mut Env) -> Type {
         match self {
             Stmt::Expr(expr) => {
-                expr.type_check(env)?;
+                check_expr(expr, Type::Unknown, env);
             }
             Stmt::Return(ret) => {
-                let expr_ty = ret.type_check(env)?;
-                if let Some((_, ret_ty)) = env.get_current_fn_type() {
-                    ensure!(
-                        ret_ty == expr_ty,
-                        "return expr type {} != fn return type {}",
-                        expr_ty,
-                        ret_ty
-                    );
-                } else {
-                    bail!("return is out of scope function");
+                // return は関数の中にしか書けない
+                let ret_ty = env.get_current_fn_type().unwrap().ret;
+                let expr_ty = check_expr(ret, ret_ty.clone(), env);
+                if !compatible(&ret_ty, &expr_ty) {
+                    let note = format!("{} which returns `{}`", env.function_note(), ret_ty);
+                    env.report(mismatched_types(ret, ret_ty, expr_ty).with_note(note));
                 }
             }
             Stmt::VariableDecl(vd) => {
-                vd.type_check(env)?;
+                vd.type_check(env);
             }
             Stmt::Assign(assign) => {
-                assign.type_check(env)?;
+                assign.type_check(env);
             }
             Stmt::IfElse(if_else) => {
-                if_else.type_check(env)?;
+                if_else.type_check(env);
             }
             Stmt::For(f) => {
-                f.type_check(env)?;
+                f.type_check(env);
+            }
+            Stmt::While(w) => {
+                w.type_check(env);
+            }
+            Stmt::Match(m) => {
+                check_match(m, false, env);
             }
+            Stmt::Break(position) | Stmt::Continue(position) if env.loop_depth == 0 => {
+                let position = *position;
+                let keyword = if matches!(self, Stmt::Break(_)) {
+                    "break"
+                } else {
+                    "continue"
+                };
+                let note = env.function_note();
+                env.report(
+                    Diagnostic::error("E0106", format!("`{}` outside of a loop", keyword))
+                        .with_primary(position, "cannot be used outside of a loop")
+                        .with_note(note),
+                );
+            }
+            Stmt::Break(_) | Stmt::Continue(_) => {}
         }
-        Ok(Type::Unit)
+        Type::Unit
     }
 }
 
 impl<'a> TypeCheck for Stmts<'a> {
-    fn type_check(&mut self, env: &mut Env) -> Result<Type> {
-        for stmt in self.0.iter_mut() {
-            stmt.type_check(env)?;
-        }
-        Ok(Type::Unit)
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        env.in_scope(|env| {
+            for stmt in self.0.iter_mut() {
+                stmt.type_check(env);
+            }
+        });
+        Type::Unit
     }
 }
 
 impl<'a> TypeCheck for FunctionDecl<'a> {
-    fn type_check(&mut self, env: &mut Env) -> Result<Type> {
+    fn type_check(&mut self, env: &mut Env) -> Type {
         env.function_id = Some(self.id.clone());
-        if env
-            .functions
-            .insert(
-                self.id.clone(),
-                (
-                    self.args.iter().map(|arg| arg.ty.clone()).collect(),
-                    self.ret_typ,
-                ),
+
+        // 引数のスコープ. 本体はさらに内側のスコープになる
+        env.in_scope(|env| {
+            for arg in self.args.iter() {
+                env.declare_var(arg.id.clone(), arg.ty.clone(), arg.position);
+            }
+            self.stmts.type_check(env);
+        });
+
+        env.function_id = None;
+
+        Type::Unit
+    }
+}
+
+/// 関数のシグネチャを登録する. 本体より先に全て登録するので, 後ろで定義された関数も呼べる
+fn declare_function(id: &str, position: Span, signature: Signature, env: &mut Env) {
+    if env.functions.contains_key(id) {
+        env.report(
+            Diagnostic::error(
+                "E0105",
+                format!("function `{}` is defined multiple times", id),
             )
-            .is_some()
-        {
-            bail!("function {} is already decleared", &self.id);
-        } else {
-            env.variables.entry(self.id.clone()).or_default();
+            .with_primary(position, "redefined here"),
+        );
+        return;
+    }
+    env.functions.insert(id.to_owned(), signature);
+}
+
+/// 構造体を型の表に登録する. フィールドの型は全て登録した後に `check_struct` で調べる
+fn declare_struct(decl: &StructDecl, env: &mut Env) {
+    if env.structs.contains_key(&decl.id) {
+        env.report(
+            Diagnostic::error(
+                "E0105",
+                format!("struct `{}` is defined multiple times", decl.id),
+            )
+            .with_primary(decl.position, "redefined here"),
+        );
+        return;
+    }
+    let mut fields: Vec<(String, Type)> = vec![];
+    for field in decl.fields.iter() {
+        if fields.iter().any(|(id, _)| *id == field.id) {
+            env.report(
+                Diagnostic::error("E0105", format!("field `{}` is already declared", field.id))
+                    .with_primary(field.position, "redeclared here"),
+            );
+            continue;
         }
-        for arg in self.args.iter() {
-            env.set_var_type(arg.id.clone(), arg.ty);
+        fields.push((field.id.clone(), field.ty.clone()));
+    }
+    env.structs.insert(decl.id.clone(), fields);
+}
+
+/// enum を型の表に登録する. 値の型は全て登録した後に `check_enum` で調べる
+fn declare_enum(decl: &EnumDecl, env: &mut Env) {
+    if env.structs.contains_key(&decl.id) || env.enums.contains_key(&decl.id) {
+        env.report(
+            Diagnostic::error(
+                "E0105",
+                format!("type `{}` is defined multiple times", decl.id),
+            )
+            .with_primary(decl.position, "redefined here"),
+        );
+        return;
+    }
+    let mut variants: Vec<(String, Vec<Type>)> = vec![];
+    for variant in decl.variants.iter() {
+        if variants.iter().any(|(id, _)| *id == variant.id) {
+            env.report(
+                Diagnostic::error(
+                    "E0105",
+                    format!("variant `{}` is already declared", variant.id),
+                )
+                .with_primary(variant.position, "redeclared here"),
+            );
+            continue;
         }
+        variants.push((variant.id.clone(), variant.fields.clone()));
+    }
+    env.enums.insert(decl.id.clone(), variants);
+}
 
-        self.stmts.type_check(env)?;
+/// `ty` の値の中に構造体か enum の `target` が入っているか. ポインタの先は数えない
+fn embeds(ty: &Type, target: &str, env: &Env, seen: &mut HashSet<String>) -> bool {
+    match ty {
+        Type::Array(elem, _) => embeds(elem, target, env, seen),
+        Type::Named(name) if name == target => true,
+        // 同じ型は一度だけ調べる
+        Type::Named(name) if seen.insert(name.clone()) => {
+            let in_struct = env.structs.get(name).map_or(false, |fields| {
+                fields.iter().any(|(_, ty)| embeds(ty, target, env, seen))
+            });
+            in_struct
+                || env.enums.get(name).map_or(false, |variants| {
+                    variants
+                        .iter()
+                        .flat_map(|(_, fields)| fields)
+                        .any(|ty| embeds(ty, target, env, seen))
+                })
+        }
+        _ => false,
+    }
+}
 
-        env.function_id = None;
+/// フィールドの型が宣言されているか, 値として自分自身を含まないか
+fn check_struct(decl: &StructDecl, env: &mut Env) {
+    for field in decl.fields.iter() {
+        check_type(&field.ty, field.position, env);
+    }
+    let mut seen = HashSet::new();
+    let recursive = decl
+        .fields
+        .iter()
+        .any(|field| embeds(&field.ty, &decl.id, env, &mut seen));
+    if recursive {
+        env.report(
+            Diagnostic::error(
+                "E0119",
+                format!("recursive struct `{}` has infinite size", decl.id),
+            )
+            .with_primary(decl.position, "contains itself without a pointer")
+            .with_help(format!("use a pointer instead: `*{}`", decl.id)),
+        );
+    }
+}
 
-        Ok(Type::Unit)
+/// バリアントの値の型が宣言されているか, 値として自分自身を含まないか
+fn check_enum(decl: &EnumDecl, env: &mut Env) {
+    for variant in decl.variants.iter() {
+        for ty in variant.fields.iter() {
+            check_type(ty, variant.position, env);
+        }
     }
+    let mut seen = HashSet::new();
+    let recursive = decl
+        .variants
+        .iter()
+        .flat_map(|variant| variant.fields.iter())
+        .any(|ty| embeds(ty, &decl.id, env, &mut seen));
+    if recursive {
+        env.report(
+            Diagnostic::error(
+                "E0119",
+                format!("recursive enum `{}` has infinite size", decl.id),
+            )
+            .with_primary(decl.position, "contains itself without a pointer")
+            .with_help(format!("use a pointer instead: `*{}`", decl.id)),
+        );
+    }
+}
+
+/// 整数型 `ty` のビット幅に切り詰める. 実行時の桁あふれと同じ値になる
+fn wrap_int(value: i128, ty: &Type) -> i128 {
+    match ty {
+        Type::Int32 => value as i32 as i128,
+        Type::Int64 => value as i64 as i128,
+        Type::UInt32 => value as u32 as i128,
+        Type::UInt64 => value as u64 as i128,
+        _ => value,
+    }
+}
+
+/// 定数式をコンパイル時に計算する. bool は 0 か 1
+/// 計算できない式や, 型に収まらない結果は報告して `None`
+fn eval_const(expr: &Expr, env: &mut Env) -> Option<i128> {
+    let ty = expr.ty();
+    let value = match expr {
+        // 範囲外のリテラルは check_range で報告している
+        Expr::IntLiteral(lit) => return Some(lit.value),
+        Expr::Const(Const::I32Const(n)) => *n as i128,
+        Expr::Const(Const::I64Const(n)) => *n as i128,
+        Expr::Const(Const::U32Const(n)) => *n as i128,
+        Expr::Const(Const::U64Const(n)) => *n as i128,
+        Expr::Const(Const::BoolConst(b)) => *b as i128,
+        Expr::Variable(var) if env.is_const(&var.id) => env.consts[&var.id].1,
+        Expr::UnaryOp(unary_op) if !matches!(unary_op.op, UnOp::Ref | UnOp::Deref) => {
+            let value = eval_const(&unary_op.expr, env)?;
+            match unary_op.op {
+                UnOp::Neg => -value,
+                UnOp::Not => (value == 0) as i128,
+                _ => wrap_int(!value, &ty),
+            }
+        }
+        Expr::BinOp(bin_op) => {
+            let left = eval_const(&bin_op.left, env)?;
+            // 実行時と同じく, 左辺で結果が決まれば右辺は計算しない
+            match bin_op.op {
+                Op::Or if left != 0 => return Some(1),
+                Op::And if left == 0 => return Some(0),
+                _ => {}
+            }
+            let right = eval_const(&bin_op.right, env)?;
+            match bin_op.op {
+                Op::Or | Op::And => (right != 0) as i128,
+                Op::Eq => (left == right) as i128,
+                Op::Neq => (left != right) as i128,
+                Op::Geq => (left >= right) as i128,
+                Op::Leq => (left <= right) as i128,
+                Op::Gt => (left > right) as i128,
+                Op::Lt => (left < right) as i128,
+                Op::Add | Op::Sub | Op::Mul => {
+                    let value = match bin_op.op {
+                        Op::Add => left.checked_add(right),
+                        Op::Sub => left.checked_sub(right),
+                        _ => left.checked_mul(right),
+                    };
+                    match value {
+                        Some(value) => value,
+                        None => {
+                            env.report(overflows(expr, &ty));
+                            return None;
+                        }
+                    }
+                }
+                Op::Div | Op::Mod if right == 0 => {
+                    env.report(
+                        Diagnostic::error("E0124", "attempt to divide by zero")
+                            .with_primary(bin_op.position, "division by zero"),
+                    );
+                    return None;
+                }
+                Op::Div => left / right,
+                Op::Mod => left % right,
+                Op::Shl | Op::Shr => {
+                    let bits = match ty {
+                        Type::Int32 | Type::UInt32 => 32,
+                        _ => 64,
+                    };
+                    if !(0..bits).contains(&right) {
+                        env.report(
+                            Diagnostic::error("E0124", format!("attempt to shift by {}", right))
+                                .with_primary(
+                                    bin_op.position,
+                                    format!("`{}` has {} bits", ty, bits),
+                                ),
+                        );
+                        return None;
+                    }
+                    // 左シフトであふれたビットは実行時と同じく捨てる
+                    match bin_op.op {
+                        Op::Shl => wrap_int(left.wrapping_shl(right as u32), &ty),
+                        _ => left >> right,
+                    }
+                }
+            }
+        }
+        Expr::Cast(cast) => wrap_int(eval_const(&cast.expr, env)?, &cast.ty),
+        _ => {
+            env.report(not_const(expr.position()));
+            return None;
+        }
+    };
+    match ty.int_range() {
+        Some((min, max)) if value < min || max < value => {
+            env.report(overflows(expr, &ty));
+            None
+        }
+        _ => Some(value),
+    }
+}
+
+/// 定数式に使えない式
+fn not_const(position: Option<Span>) -> Diagnostic {
+    label_at(
+        Diagnostic::error("E0124", "cannot evaluate this expression at compile time"),
+        position,
+        "not a constant expression",
+    )
+    .with_note("constants can only use literals, other constants, operators and `as`")
+}
+
+/// 定数式 `expr` の結果が `ty` に収まらない
+fn overflows(expr: &Expr, ty: &Type) -> Diagnostic {
+    label_at(
+        Diagnostic::error("E0124", "this arithmetic operation overflows"),
+        expr.position(),
+        format!("the result does not fit in `{}`", ty),
+    )
+}
+
+/// const と static の型と初期値を調べる. 初期値はコンパイル時に計算しておく
+fn check_global(decl: &mut GlobalDecl, env: &mut Env) {
+    if !decl.ty.is_integer() && decl.ty != Type::Bool {
+        check_expr(&mut decl.init, Type::Unknown, env);
+        env.report(
+            Diagnostic::error(
+                "E0124",
+                format!("`{}` cannot have type `{}`", decl.id, decl.ty),
+            )
+            .with_primary(
+                decl.position,
+                "constants and statics must be integers or `bool`",
+            ),
+        );
+        decl.ty = Type::Error;
+    } else {
+        let found = check_expr(&mut decl.init, decl.ty.clone(), env);
+        if !compatible(&decl.ty, &found) {
+            env.report(
+                mismatched_types(&decl.init, decl.ty.clone(), found).with_secondary(
+                    decl.position,
+                    format!("`{}` is declared as `{}`", decl.id, decl.ty),
+                ),
+            );
+        } else if found != Type::Error {
+            // static の初期値も LLVM の global の初期値にするので計算できないといけない
+            if let Some(value) = eval_const(&decl.init, env) {
+                decl.value = value;
+                if !decl.mutable {
+                    env.consts.insert(decl.id.clone(), (decl.ty.clone(), value));
+                }
+            }
+        }
+    }
+    env.declare_var(decl.id.clone(), decl.ty.clone(), decl.position);
+}
+
+/// 宣言に書いた型の, 配列の長さの定数を値にする
+fn resolve_item_types(item: &mut Item, env: &mut Env) {
+    match item {
+        Item::Function(function) => {
+            for arg in function.args.iter_mut() {
+                resolve_len(&mut arg.ty, arg.position, env);
+            }
+            resolve_len(&mut function.ret_typ, function.position, env);
+        }
+        Item::ExternFn(decl) => {
+            for arg in decl.args.iter_mut() {
+                resolve_len(&mut arg.ty, arg.position, env);
+            }
+            resolve_len(&mut decl.ret_typ, decl.position, env);
+        }
+        Item::Struct(decl) => {
+            for field in decl.fields.iter_mut() {
+                resolve_len(&mut field.ty, field.position, env);
+            }
+        }
+        Item::Enum(decl) => {
+            for variant in decl.variants.iter_mut() {
+                for ty in variant.fields.iter_mut() {
+                    resolve_len(ty, variant.position, env);
+                }
+            }
+        }
+        Item::Global(_) => {}
+    }
+}
+
+/// 引数と戻り値の型が宣言されているか
+fn check_signature(args: &[Variable], ret: &Type, position: Span, env: &mut Env) {
+    for arg in args {
+        check_type(&arg.ty, arg.position, env);
+    }
+    check_type(ret, position, env);
 }
 
 impl<'a> TypeCheck for Program<'a> {
-    fn type_check(&mut self, env: &mut Env) -> Result<Type> {
-        for function in self.0.iter_mut() {
-            function.type_check(env)?;
+    fn type_check(&mut self, env: &mut Env) -> Type {
+        // const と static はどの関数からも見えるように一番外側のスコープに置く
+        env.in_scope(|env| {
+            // 定数は配列の長さに使えるので, 他の宣言より先に計算する
+            for item in self.0.iter_mut() {
+                if let Item::Global(decl) = item {
+                    check_global(decl, env);
+                }
+            }
+            for item in self.0.iter_mut() {
+                resolve_item_types(item, env);
+            }
+            // 構造体と enum はどこからでも使えるように, 関数より先に全て登録する
+            for decl in self.structs() {
+                declare_struct(decl, env);
+            }
+            for decl in self.enums() {
+                declare_enum(decl, env);
+            }
+            for decl in self.structs() {
+                check_struct(decl, env);
+            }
+            for decl in self.enums() {
+                check_enum(decl, env);
+            }
+            for item in self.0.iter() {
+                match item {
+                    Item::Function(function) => {
+                        check_signature(&function.args, &function.ret_typ, function.position, env);
+                        declare_function(
+                            &function.id,
+                            function.position,
+                            Signature::of_function(function),
+                            env,
+                        )
+                    }
+                    Item::ExternFn(decl) => {
+                        check_signature(&decl.args, &decl.ret_typ, decl.position, env);
+                        declare_function(&decl.id, decl.position, Signature::of_extern(decl), env)
+                    }
+                    Item::Struct(_) | Item::Enum(_) | Item::Global(_) => {}
+                }
+            }
+            for item in self.0.iter_mut() {
+                if let Item::Function(function) = item {
+                    function.type_check(env);
+                }
+            }
+        });
+        Type::Unit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipulang_parser::{ast::program_parser, nodes::Span};
+
+    fn check(code: &str) -> Result<(), Vec<Diagnostic>> {
+        let program = program_parser(Span::new(code)).unwrap();
+        type_check(program).map(|_| ())
+    }
+
+    #[test]
+    fn test_unary_op() {
+        let codes = vec![
+            "fn main(): i32 { return -1; }",
+            "fn main(): i64 { var a: i64 = 2_i64; return -a; }",
+            "fn main(): bool { return !(1 < 2); }",
+            "fn main(): i32 { return ~0 + -2147483648; }",
+        ];
+        for code in codes {
+            assert!(check(code).is_ok(), "{}", code);
+        }
+
+        let codes = vec![
+            "fn main(): bool { return -(1 < 2); }",
+            "fn main(): i32 { return !1; }",
+            "fn main(): bool { return ~(1 < 2); }",
+        ];
+        for code in codes {
+            assert!(check(code).is_err(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_break_continue() {
+        let codes = vec![
+            "fn main(): unit { while (1 < 2) { break; } }",
+            "fn main(): unit { while (1 < 2) { if (1 < 2) { continue; } } }",
+            "fn main(): unit { for (var i: i32 = 0; i < 2; i = i + 1;) { break; continue; } }",
+        ];
+        for code in codes {
+            assert!(check(code).is_ok(), "{}", code);
+        }
+
+        let codes = vec![
+            "fn main(): unit { break; }",
+            "fn main(): unit { if (1 < 2) { continue; } }",
+            "fn main(): unit { while (1 < 2) { } break; }",
+            "fn main(): unit { while (1) { } }",
+        ];
+        for code in codes {
+            assert!(check(code).is_err(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_scope() {
+        let codes = vec![
+            // 外側の変数を隠せる
+            "fn main(): i32 { var a: i32 = 1; if (1 < 2) { var a: bool = 1 < 2; } return a; }",
+            "fn main(): unit { var a: i32 = 1; while (1 < 2) { var a: i32 = a + 1; } }",
+            "fn f(a: i32): i32 { var a: i64 = 1_i64; return 0; }",
+            "fn main(): unit { for (var i: i32 = 0; i < 2; i = i + 1;) { var i: bool = 1 < 2; } }",
+            // 別々のブロックで同じ名前
+            "fn main(): unit { if (1 < 2) { var a: i32; } else { var a: i32; } }",
+            "fn main(): unit { for (var i: i32 = 0; i < 2; i = i + 1;) {} for (var i: i32 = 0; i < 2; i = i + 1;) {} }",
+        ];
+        for code in codes {
+            assert!(check(code).is_ok(), "{}", code);
+        }
+
+        let codes = vec![
+            // ブロックの外には漏れない
+            "fn main(): i32 { if (1 < 2) { var a: i32 = 1; } return a; }",
+            "fn main(): i32 { for (var i: i32 = 0; i < 2; i = i + 1;) {} return i; }",
+            "fn main(): i32 { while (1 < 2) { var a: i32; } return a; }",
+            // 同じスコープでの再宣言
+            "fn main(): unit { var a: i32; var a: i32; }",
+            "fn f(a: i32, a: i32): unit { }",
+            // 隠した変数の型が使われる
+            "fn main(): i32 { var a: i32 = 1; if (1 < 2) { var a: bool = 1 < 2; a = 2; } return a; }",
+        ];
+        for code in codes {
+            assert!(check(code).is_err(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_forward_reference() {
+        let code = r#"
+fn main(): bool {
+    return even(10);
+}
+fn even(n: i32): bool {
+    if (n == 0) {
+        return 1_bool;
+    }
+    return odd(n - 1);
+}
+fn odd(n: i32): bool {
+    if (n == 0) {
+        return 0_bool;
+    }
+    return even(n - 1);
+}
+"#;
+        assert!(check(code).is_ok());
+        assert!(check("fn f(): i32 { return 0; } fn f(): i32 { return 1; }").is_err());
+        assert!(check("fn main(): i32 { return g(1); } fn g(): i32 { return 0; }").is_err());
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let code = "fn main(): i32 {\n    var a: bool = 1 < 2;\n    return a + 1;\n}";
+        let diag = &check(code).unwrap_err()[0];
+        assert_eq!(diag.to_string(), "3:14: error[E0101]: mismatched types");
+        let labels: Vec<(usize, bool, &str)> = diag
+            .labels
+            .iter()
+            .map(|l| (l.location.column, l.primary, l.message.as_str()))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                (14, true, "`bool` and `int32` cannot be mixed"),
+                (12, false, "this is `bool`"),
+                (16, false, "this is `int32`"),
+            ]
+        );
+
+        let diag = &check("fn main(): unit {\n    b = 1;\n}").unwrap_err()[0];
+        assert_eq!(
+            diag.to_string(),
+            "2:5: error[E0102]: cannot find variable `b`"
+        );
+    }
+
+    #[test]
+    fn test_all_errors() {
+        let code = r#"
+fn f(a: i32): bool {
+    if (a) {
+        return 1_bool;
+    } else {
+        var b: i32 = 1_bool;
+    }
+    return a;
+}
+fn main(): i32 {
+    var c: i32 = x + 1 + 2;
+    c = f(c, 1) + 1;
+    while (1 < 2) {
+        break;
+    }
+    continue;
+    return c;
+}
+"#;
+        let errors: Vec<String> = check(code)
+            .unwrap_err()
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "3:9: error[E0101]: mismatched types",
+                "6:9: error[E0101]: mismatched types",
+                "8:12: error[E0101]: mismatched types",
+                // x が見つからないエラーの後, x + 1 + 2 ではエラーにしない
+                "11:18: error[E0102]: cannot find variable `x`",
+                "12:9: error[E0104]: function `f` takes 1 arguments but 2 were given",
+                "12:17: error[E0101]: mismatched types",
+                "16:5: error[E0106]: `continue` outside of a loop",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unsigned() {
+        let codes = vec![
+            "fn main(): u32 { var a: u32 = 10_u32; return a / 3_u32 + a % 2_u32; }",
+            "fn main(): bool { var a: u64 = 10_u64; return a > 3_u64; }",
+            "fn main(): u64 { return ~0_u64 >> 1; }",
+            "fn main(): i32 { var a: u64 = 3_u64; return 1 << a; }",
+            "fn f(a: u32): u32 { return a; } fn main(): u32 { return f(1_u32); }",
+        ];
+        for code in codes {
+            assert!(check(code).is_ok(), "{}", code);
+        }
+
+        let codes = vec![
+            "fn main(): u32 { return -1; }",
+            "fn main(): u32 { var a: i32 = 1; return 1_u32 + a; }",
+            "fn main(): u64 { return 1_u32; }",
+            "fn main(): u32 { return -(1_u32); }",
+            "fn main(): bool { return 1_bool << 1; }",
+        ];
+        for code in codes {
+            assert!(check(code).is_err(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_cast() {
+        let codes = vec![
+            "fn main(): i64 { var a: i32 = 1; var b: i64 = 2_i64; return a as i64 + b; }",
+            "fn main(): i32 { return 10_u64 as i32; }",
+            "fn main(): u32 { return -1 as u32; }",
+            "fn main(): i32 { return (1 < 2) as i32; }",
+            "fn main(): bool { return 1_bool as bool; }",
+        ];
+        for code in codes {
+            assert!(check(code).is_ok(), "{}", code);
+        }
+
+        let codes = vec![
+            "fn main(): bool { return 1 as bool; }",
+            "fn main(): i64 { return 1 as i32; }",
+            "fn main(): i64 { var a: i32 = 1; return a + 1_i64; }",
+        ];
+        for code in codes {
+            assert!(check(code).is_err(), "{}", code);
+        }
+
+        // 同じ型でも整数と bool 以外はキャストできない
+        let codes = vec![
+            "fn f(): unit { } fn main(): unit { f() as unit; }",
+            "fn main(): unit { var s = \"a\"; var t = s as string; }",
+            "fn main(): unit { var a = 1; var p = &a as *i32; }",
+            "fn main(): unit { var a: [i32; 2] = [1, 2]; var b = a as [i32; 2]; }",
+            "struct P { x: i32 } fn main(): unit { var p = P { x: 1 }; var q = p as P; }",
+        ];
+        for code in codes {
+            let errors = check(code).unwrap_err();
+            assert_eq!(errors[0].code, "E0109", "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_infer() {
+        let code = "fn main(): i64 { var a = 1_i64; var b = a * 2; var c = b > 1; return b; }";
+        let program = type_check(program_parser(Span::new(code)).unwrap()).unwrap();
+        let types: Vec<Type> = program
+            .functions()
+            .next()
+            .unwrap()
+            .stmts
+            .0
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::VariableDecl(var_decl) => Some(var_decl.ty.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(types, vec![Type::Int64, Type::Int64, Type::Bool]);
+
+        // 整数リテラルは文脈の型になる
+        let codes = vec![
+            "fn main(): i32 { var a = 1; return a; }",
+            "fn main(): i64 { var n: i64 = 5; n = 6; return n + 1; }",
+            "fn main(): u32 { return 1; }",
+            "fn main(): u32 { return 1_u32 + 1; }",
+            "fn f(a: u64): u64 { return a; } fn main(): u64 { return f(1); }",
+        ];
+        for code in codes {
+            assert!(check(code).is_ok(), "{}", code);
+        }
+
+        let codes = vec![
+            "fn main(): i64 { var a = 1; return a; }",
+            "fn main(): unit { var a = 1_bool; a = 1; }",
+            "fn main(): unit { var a: u32 = -1; }",
+        ];
+        for code in codes {
+            assert!(check(code).is_err(), "{}", code);
+        }
+
+        let errors = check("fn main(): unit { var a; a = 1; }").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].to_string(),
+            "1:19: error[E0110]: type annotations needed for `a`"
+        );
+
+        let codes = vec![
+            "fn f(): unit { } fn main(): i32 { var x = f(); return 0; }",
+            "fn f(): unit { } fn main(): i32 { var x: unit = f(); return 0; }",
+            "fn main(): i32 { var x: unit; return 0; }",
+        ];
+        for code in codes {
+            let errors = check(code).unwrap_err();
+            assert_eq!(errors.len(), 1, "{}", code);
+            assert!(
+                errors[0]
+                    .to_string()
+                    .ends_with("error[E0125]: variable `x` has type `unit`"),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn test_string() {
+        let codes = vec![
+            r#"fn f(s: string): string { return s; } fn main(): unit { var s = f("a\n"); s = "b"; }"#,
+            "fn main(): i32 { var c: u64 = 'a'; return 'b' - '\\n'; }",
+        ];
+        for code in codes {
+            assert!(check(code).is_ok(), "{}", code);
+        }
+
+        let codes = vec![
+            r#"fn main(): bool { return "a" == "a"; }"#,
+            r#"fn main(): i32 { return "a" as i32; }"#,
+            r#"fn main(): unit { var s: string = 'a'; }"#,
+            r#"fn main(): unit { putchar("a"); }"#,
+        ];
+        for code in codes {
+            assert!(check(code).is_err(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_builtins() {
+        let code = r#"fn main(): i32 { print_i32(1); print_i64(2); print_bool(1 < 2); print_str("a"); println(); return read_i32(); }"#;
+        assert!(check(code).is_ok());
+        assert!(check("fn main(): unit { print_i32(1_i64); }").is_err());
+        assert!(check("fn main(): unit { print_str(1); }").is_err());
+        assert!(check("fn main(): unit { println(1); }").is_err());
+    }
+
+    #[test]
+    fn test_extern_fn() {
+        let code = r#"
+extern fn abs(x: i32): i32;
+extern fn printf(format: string, ...): i32;
+fn main(): i32 {
+    printf("%d %s\n", abs(-1), "a");
+    printf("no args\n");
+    var a: [i32; 2] = [1, 2];
+    printf("%d %u %p\n", a[0] < 1, 1_u64, &a);
+    return abs(main());
+}
+"#;
+        assert!(check(code).is_ok());
+
+        let codes = vec![
+            "extern fn abs(x: i32): i32; fn main(): i32 { return abs(1_i64); }",
+            "extern fn abs(x: i32): i32; fn main(): i32 { return abs(); }",
+            "extern fn printf(format: string, ...): i32; fn main(): i32 { return printf(); }",
+            "extern fn printf(format: string, ...): i32; fn main(): i32 { return printf(1); }",
+            "extern fn printf(format: string, ...): i32; fn main(): i32 { return printf(\"%d\", println()); }",
+            "extern fn printf(format: string, ...): i32; fn main(): i32 { var a: [i32; 2] = [1, 2]; return printf(\"%d\", a); }",
+            // 組み込み関数と同じ名前は使えない
+            "extern fn putchar(c: i32): i32; fn main(): i32 { return 0; }",
+            "extern fn f(): i32; fn f(): i32 { return 0; }",
+        ];
+        for code in codes {
+            assert!(check(code).is_err(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_array() {
+        let code = r#"
+fn sum(a: [i64; 3]): i64 {
+    var s = 0_i64;
+    for (var i = 0; i < 3; i = i + 1;) {
+        s = s + a[i];
+    }
+    return s;
+}
+fn main(): i64 {
+    var a: [i64; 3] = [1, 2, 3];
+    var b = [[1, 2], [3, -4]];
+    var c: [bool; 2];
+    var e: [i32; 0] = [];
+    a[0] = a[1] + 1;
+    b[1][0] = b[0][1] * 2;
+    c[1] = b[0][0] < b[1][1];
+    return sum(a) + b[1][1] as i64;
+}
+"#;
+        let program = type_check(program_parser(Span::new(code)).unwrap()).unwrap();
+        let types: Vec<Type> = program
+            .functions()
+            .nth(1)
+            .unwrap()
+            .stmts
+            .0
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::VariableDecl(var_decl) => Some(var_decl.ty.clone()),
+                _ => None,
+            })
+            .collect();
+        let row = Type::Array(Box::new(Type::Int32), 2);
+        assert_eq!(types[1], Type::Array(Box::new(row), 2));
+
+        let codes = vec![
+            // 要素の型, 長さが合わない
+            "fn main(): unit { var a: [i32; 2] = [1, 2, 3]; }",
+            "fn main(): unit { var a = [1, 2 < 3]; }",
+            "fn main(): unit { var a: [i32; 2] = [1_i64, 2]; }",
+            "fn main(): unit { var a = [1, 2]; var b: [i64; 2] = a; }",
+            // 添字
+            "fn main(): unit { var a = [1, 2]; a[2] = 0; }",
+            "fn main(): unit { var a = [1, 2]; a[-1] = 0; }",
+            "fn main(): unit { var a = [1, 2]; a[1 < 2] = 0; }",
+            "fn main(): unit { var a = 1; a[0] = 0; }",
+            "fn main(): unit { var a = [1, 2]; a[0] = 1 < 2; }",
+            // 配列は演算できない
+            "fn main(): unit { var a = [1, 2]; var b = a + a; }",
+            "fn main(): unit { var a = [1, 2] == [1, 2]; }",
+        ];
+        for code in codes {
+            assert!(check(code).is_err(), "{}", code);
+        }
+
+        let errors = check("fn main(): unit { var a = [1, 2]; a[2] = 0; }").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "E0112");
+    }
+
+    #[test]
+    fn test_pointer() {
+        let code = r#"
+fn swap(a: *i64, b: *i64): unit {
+    var t = *a;
+    *a = *b;
+    *b = t;
+}
+fn main(): i64 {
+    var x: i64 = 1;
+    var y: i64 = 2;
+    swap(&x, &y);
+    var p = &x;
+    var pp: **i64 = &p;
+    **pp = *p + 1;
+    var a = [1, 2, 3];
+    var q: *[i32; 3] = &a;
+    (*q)[0] = 5;
+    var r = &a[1];
+    *r = 7;
+    return x;
+}
+"#;
+        assert!(check(code).is_ok());
+
+        let codes = vec![
+            "fn main(): unit { var x = 1; var p: *i64 = &x; }",
+            "fn main(): unit { var x = 1; var p = &x; *p = 1 < 2; }",
+            "fn main(): unit { var x = 1; var p = &x; var q = p + 1; }",
+            "fn main(): unit { var x = 1; var p = &x; var b = p == p; }",
+        ];
+        for code in codes {
+            assert!(check(code).is_err(), "{}", code);
+        }
+
+        let codes = vec![
+            ("fn main(): unit { var p = &1; }", "E0114"),
+            (
+                "fn f(): i32 { return 0; } fn main(): unit { var p = &f(); }",
+                "E0114",
+            ),
+            ("fn main(): unit { var x = 1; var y = *x; }", "E0115"),
+        ];
+        for (code, expected) in codes {
+            let errors = check(code).unwrap_err();
+            assert_eq!(errors.len(), 1, "{}", code);
+            assert_eq!(errors[0].code, expected, "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_struct() {
+        let code = r#"
+fn norm1(p: Point): i32 {
+    return p.x + p.y;
+}
+struct Point { x: i32, y: i32 }
+struct Segment { from: Point, to: Point, points: [Point; 2] }
+struct Node { value: i64, next: *Node }
+fn mid(s: Segment): Point {
+    return Point { y: (s.from.y + s.to.y) / 2, x: (s.from.x + s.to.x) / 2 };
+}
+fn main(): i32 {
+    var p = Point { x: 1, y: 2 };
+    var s = Segment { from: p, to: Point { x: 3, y: 4 }, points: [p, p] };
+    s.to.x = 5;
+    s.points[1].y = mid(s).x;
+    var q = &s.from;
+    (*q).x = 7;
+    var n: Node;
+    n.value = 3;
+    n.next = &n;
+    return norm1(s.from) + (*n.next).value as i32;
+}
+"#;
+        assert!(check(code).is_ok());
+
+        let codes = vec![
+            // 型が合わない
+            "struct P { x: i32 } fn main(): unit { var p = P { x: 1 < 2 }; }",
+            "struct P { x: i32 } fn main(): unit { var p = P { x: 1 }; p.x = 1_i64; }",
+            "struct P { x: i32 } struct Q { x: i32 } fn main(): unit { var p: P = Q { x: 1 }; }",
+            "struct P { x: i32 } fn main(): unit { var p = P { x: 1 }; var b = p == p; }",
+            // 名前
+            "struct P { x: i32 } struct P { y: i32 }",
+            "struct P { x: i32, x: i64 }",
+            "struct P { x: i32 } fn main(): unit { var p = P { x: 1, y: 2 }; }",
+        ];
+        for code in codes {
+            assert!(check(code).is_err(), "{}", code);
+        }
+
+        let codes = vec![
+            ("fn f(p: Q): unit {}", "E0116"),
+            ("fn main(): unit { var p = Q { x: 1 }; }", "E0116"),
+            ("struct P { x: i32 } fn main(): i32 { var p = P { x: 1 }; return p.y; }", "E0117"),
+            ("struct P { x: i32 } fn main(): i32 { var p = P { x: 1 }; var q = &p; return q.x; }", "E0117"),
+            ("fn main(): i32 { var a = 1; return a.x; }", "E0117"),
+            ("struct P { x: i32, y: i32 } fn main(): unit { var p = P { y: 1 }; }", "E0118"),
+            ("struct P { x: i32 } fn main(): unit { var p = P { x: 1, x: 2 }; }", "E0126"),
+            ("struct A { b: [B; 2] } struct B { a: A }", "E0119"),
+        ];
+        for (code, expected) in codes {
+            let errors = check(code).unwrap_err();
+            assert_eq!(errors[0].code, expected, "{}", code);
+        }
+        // 見つからない型を使った式ではエラーを重ねない
+        let errors = check("fn f(p: Q): i32 { return p.x; }").unwrap_err();
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_enum() {
+        let code = r#"
+enum Color { Red, Green, Blue }
+enum Shape { Circle(i32), Rect(i32, i32), Empty }
+struct Canvas { shape: Shape, color: Color }
+enum List { Cons(i64, *List), Nil }
+fn area(s: Shape): i32 {
+    match (s) {
+        Shape::Circle(r) => { return 3 * r * r; }
+        Shape::Rect(w, h) => { return w * h; }
+        Shape::Empty => { return 0; }
+    }
+}
+fn main(): i64 {
+    var c = Canvas { shape: Shape::Rect(2, 3), color: Color::Red };
+    var code = match (c.color) { Color::Red => 1_i64, _ => 2 };
+    match (c.shape) {
+        Shape::Rect(w, _) => print_i32(w),
+        _ => {}
+    }
+    var tail = List::Nil;
+    var list = List::Cons(1, &tail);
+    return code + match (list) { List::Cons(v, _) => v, List::Nil => 0 } + area(c.shape) as i64;
+}
+"#;
+        assert!(check(code).is_ok(), "{:?}", check(code));
+
+        let codes = vec![
+            ("enum E { A } fn f(): E { return F::A; }", "E0116"),
+            ("enum E { A } fn f(): E { return E::B; }", "E0121"),
+            ("enum E { A(i32) } fn f(): E { return E::A; }", "E0104"),
+            ("enum E { A(i32) } fn f(): E { return E::A(1 < 2); }", "E0101"),
+            ("enum E { A, B } fn f(e: E): unit { match (e) { E::A => {} } }", "E0120"),
+            ("enum E { A(i32) } fn f(e: E): unit { match (e) { E::A(x, y) => {} } }", "E0104"),
+            ("enum E { A } enum F { A } fn f(e: E): unit { match (e) { F::A => {} } }", "E0101"),
+            ("fn f(): unit { match (1) { _ => {} } }", "E0122"),
+            ("enum E { A, B } fn f(e: E): i32 { return match (e) { E::A => 1, E::B => 1 < 2 }; }", "E0101"),
+            ("enum E { A } fn f(e: E): i32 { return e.x; }", "E0117"),
+            // unit の腕しかない match は値にできない
+            ("enum E { A, B } fn f(e: E): unit { var x = match (e) { E::A => println(), _ => println() }; }", "E0125"),
+            ("enum E { A } fn f(e: E): i32 { return match (e) { E::A => println() }; }", "E0101"),
+            ("enum E { A, A }", "E0105"),
+            ("struct E { x: i32 } enum E { A }", "E0105"),
+            ("enum E { A(F) } struct F { e: E }", "E0119"),
+            ("enum E { A(G) }", "E0116"),
+        ];
+        for (code, expected) in codes {
+            let errors = check(code).unwrap_err();
+            assert_eq!(errors[0].code, expected, "{}", code);
+        }
+        let errors =
+            check("enum E { A, B, C } fn f(e: E): unit { match (e) { E::B => {} } }").unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "non-exhaustive patterns: `E::A`, `E::C` not covered"
+        );
+
+        // 前の腕で全て当てはまる腕は警告
+        let code = r#"enum E { A, B }
+fn f(e: E): unit {
+    match (e) { E::A => {} E::A => {} _ => {} E::B => {} }
+}"#;
+        let program = program_parser(Span::new(code)).unwrap();
+        let (_, warnings) = type_check_with_warnings(program).unwrap();
+        let warnings: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "3:28: warning[W0002]: unreachable pattern",
+                "3:47: warning[W0002]: unreachable pattern",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_global() {
+        let code = r#"
+const N: i32 = 10 * 3;
+const M: u64 = (N as u64 << 40) / 7 + 0xff % 2;
+const MASK: u32 = ~0;
+const DEBUG: bool = N > 10 && !(M == 0);
+const SAFE: bool = N == 0 && 1 / 0 == 0 || N > 0 || 1 % 0 == 0;
+static mut counter: i32 = -N;
+struct Buffer { data: [i32; N], len: i32 }
+fn sum(a: [i32; N]): i32 {
+    var s = 0;
+    for (var i = 0; i < N; i = i + 1;) {
+        s = s + a[i];
+    }
+    counter = counter + 1;
+    return s;
+}
+fn main(): i32 {
+    var b: Buffer;
+    var grid: [[u32; N]; 2];
+    var p = &counter;
+    *p = 0;
+    var N = 1;
+    N = 2;
+    return sum(b.data) + counter;
+}
+"#;
+        let program = program_parser(Span::new(code)).unwrap();
+        let program = type_check(program).unwrap();
+        let values: Vec<(&str, i128)> = program
+            .globals()
+            .map(|decl| (decl.id.as_str(), decl.value))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("N", 30),
+                ("M", (30 << 40) / 7 + 1),
+                ("MASK", u32::MAX as i128),
+                ("DEBUG", 1),
+                ("SAFE", 1),
+                ("counter", -30),
+            ]
+        );
+        let array = Type::Array(Box::new(Type::Int32), 30);
+        assert_eq!(program.functions().next().unwrap().args[0].ty, array);
+
+        let codes = vec![
+            ("const N: i32 = 1; fn main(): unit { N = 2; }", "E0123"),
+            ("const N: i32 = 1; fn main(): unit { var p = &N; }", "E0114"),
+            ("fn f(a: [i32; N]): unit {}", "E0102"),
+            ("static mut n: i32 = 1; fn f(a: [i32; n]): unit {}", "E0102"),
+            ("const N: bool = 1 < 2; fn f(a: [i32; N]): unit {}", "E0124"),
+            ("const N: i32 = -1; struct S { a: [i32; N] }", "E0124"),
+            (
+                "const N: u64 = 4294967297; struct S { a: [i32; N] }",
+                "E0124",
+            ),
+            ("const N: i32 = 1 < 2;", "E0101"),
+            ("const N: i32 = 2147483647 + 1;", "E0124"),
+            (
+                "const A: u64 = 18446744073709551615; const B: u64 = A * A;",
+                "E0124",
+            ),
+            ("const N: i32 = 1 / (1 - 1);", "E0124"),
+            ("const T: bool = 1 < 2 && 1 / 0 == 0;", "E0124"),
+            ("const N: i32 = 1 << 32;", "E0124"),
+            ("const N: i32 = read_i32();", "E0124"),
+            ("fn f(): i32 { return 1; } const N: i32 = f();", "E0124"),
+            ("const N: i32 = 1 + g(2);", "E0124"),
+            ("static mut a: i32 = 1; static mut b: i32 = a;", "E0124"),
+            ("const S: string = \"s\";", "E0124"),
+            ("const N: i32 = 1; static mut N: i32 = 2;", "E0105"),
+        ];
+        for (code, expected) in codes {
+            let errors = check(code).unwrap_err();
+            assert_eq!(errors[0].code, expected, "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_int_literal() {
+        let codes = vec![
+            "fn main(): i64 { var a: i64 = 3000000000; return a; }",
+            "fn main(): u32 { return 0xffff_ffff; }",
+            "fn main(): i64 { var n: i64 = 1 + 2 * 3; return -n - (4 << 40); }",
+            "fn main(): u64 { var a: u64 = 1; return a + 0b1010; }",
+            "fn main(): bool { var a: u32 = 5; return 10 > a; }",
+            "fn main(): i32 { var a = 1 + 2; var b: i32 = a; return b; }",
+            "fn main(): i32 { return 1 << 2_u64; }",
+        ];
+        for code in codes {
+            assert!(check(code).is_ok(), "{}", code);
         }
-        Ok(Type::Unit)
+
+        let code = r#"fn main(): unit {
+    var a: i32 = 3000000000;
+    var b: u32 = -1;
+    var c: u32 = -(1);
+    var d = 99999999999999999999999999999999999999999;
+    var e = 3000000000_i32;
+}"#;
+        let errors = check(code).unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "2:18: error[E0111]: integer literal out of range for `int32`",
+                "3:18: error[E0111]: integer literal out of range for `uint32`",
+                "4:18: error[E0107]: cannot apply unary operator to type `uint32`",
+                "5:13: error[E0111]: integer literal out of range for `int32`",
+                "6:13: error[E0111]: integer literal out of range for `int32`",
+            ]
+        );
+        assert_eq!(
+            errors[0].help.as_deref(),
+            Some("use a type that can hold it, such as `int64`")
+        );
+        assert_eq!(errors[0].primary().unwrap().location.len, 10);
     }
 }